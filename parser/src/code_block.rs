@@ -1,30 +1,72 @@
-use pulldown_cmark::{Event, Parser, Tag};
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag};
 use std::ops::Range;
 
 #[derive(Debug)]
 pub struct ColorCodeBlocks {
     code: Vec<Range<usize>>,
+    blocks: Vec<CodeBlock>,
+}
+
+/// A fenced code block.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// The info string after the opening fence, e.g. `rust,ignore`.
+    pub info: String,
+    /// The range of the contents of the block, without the fences.
+    pub contents: Range<usize>,
+}
+
+impl CodeBlock {
+    /// Whether the block is marked as Rust code.
+    pub fn is_rust(&self) -> bool {
+        self.info
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .next()
+            == Some("rust")
+    }
 }
 
 impl ColorCodeBlocks {
     pub fn new(s: &str) -> ColorCodeBlocks {
         let mut code = Vec::new();
+        let mut blocks = Vec::new();
         let mut parser = Parser::new(s).into_offset_iter();
         while let Some((event, range)) = parser.next() {
-            if let Event::Start(Tag::CodeBlock(_)) = event {
+            if let Event::Start(Tag::CodeBlock(kind)) = event {
                 let start = range.start;
+                let mut contents: Option<Range<usize>> = None;
                 while let Some((event, range)) = parser.next() {
-                    if let Event::End(Tag::CodeBlock(_)) = event {
-                        code.push(start..range.end);
-                        break;
+                    match event {
+                        Event::Text(_) => {
+                            contents = Some(match contents {
+                                Some(contents) => contents.start..range.end,
+                                None => range,
+                            });
+                        }
+                        Event::End(Tag::CodeBlock(_)) => {
+                            code.push(start..range.end);
+                            break;
+                        }
+                        _ => {}
                     }
                 }
+                if let CodeBlockKind::Fenced(info) = kind {
+                    blocks.push(CodeBlock {
+                        info: info.trim().to_owned(),
+                        contents: contents.unwrap_or(start..start),
+                    });
+                }
             } else if let Event::Code(_) = event {
                 code.push(range);
             }
         }
 
-        ColorCodeBlocks { code }
+        ColorCodeBlocks { code, blocks }
+    }
+
+    /// The fenced code blocks, in order of appearance.
+    pub fn blocks(&self) -> &[CodeBlock] {
+        &self.blocks
     }
 
     pub fn overlaps_code(&self, region: Range<usize>) -> Option<Range<usize>> {
@@ -163,3 +205,25 @@ fn cbs_8() {
         [Code::Yes("`one`"), Code::No(" not "), Code::Yes("`two`")]
     );
 }
+
+#[test]
+fn cbs_fenced() {
+    let s = "
+```rust,ignore
+fn main() {}
+```
+
+    indented
+
+```
+plain
+```
+";
+    let cbs = ColorCodeBlocks::new(s);
+    let blocks = cbs.blocks();
+    assert_eq!(blocks.len(), 2);
+    assert!(blocks[0].is_rust());
+    assert_eq!(&s[blocks[0].contents.clone()], "fn main() {}\n");
+    assert!(!blocks[1].is_rust());
+    assert_eq!(&s[blocks[1].contents.clone()], "plain\n");
+}
@@ -3,12 +3,15 @@ use crate::error::Error;
 use crate::token::{Token, Tokenizer};
 
 pub mod assign;
+pub mod concern;
+pub mod fcp;
 pub mod glacier;
 pub mod nominate;
 pub mod ping;
 pub mod prioritize;
 pub mod relabel;
 pub mod second;
+pub mod withdraw;
 
 pub fn find_commmand_start(input: &str, bot: &str) -> Option<usize> {
     input.find(&format!("@{}", bot))
@@ -23,6 +26,9 @@ pub enum Command<'a> {
     Prioritize(Result<prioritize::PrioritizeCommand, Error<'a>>),
     Second(Result<second::SecondCommand, Error<'a>>),
     Glacier(Result<glacier::GlacierCommand, Error<'a>>),
+    Concern(Result<concern::ConcernCommand, Error<'a>>),
+    Withdraw(Result<withdraw::WithdrawCommand, Error<'a>>),
+    Fcp(Result<fcp::FcpCommand, Error<'a>>),
 }
 
 #[derive(Debug)]
@@ -110,6 +116,21 @@ impl<'a> Input<'a> {
             Command::Glacier,
             &original_tokenizer,
         ));
+        success.extend(parse_single_command(
+            concern::ConcernCommand::parse,
+            Command::Concern,
+            &original_tokenizer,
+        ));
+        success.extend(parse_single_command(
+            withdraw::WithdrawCommand::parse,
+            Command::Withdraw,
+            &original_tokenizer,
+        ));
+        success.extend(parse_single_command(
+            fcp::FcpCommand::parse,
+            Command::Fcp,
+            &original_tokenizer,
+        ));
 
         if success.len() > 1 {
             panic!(
@@ -164,6 +185,9 @@ impl<'a> Command<'a> {
             Command::Prioritize(r) => r.is_ok(),
             Command::Second(r) => r.is_ok(),
             Command::Glacier(r) => r.is_ok(),
+            Command::Concern(r) => r.is_ok(),
+            Command::Withdraw(r) => r.is_ok(),
+            Command::Fcp(r) => r.is_ok(),
         }
     }
 
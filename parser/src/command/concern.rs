@@ -0,0 +1,117 @@
+//! The concern command parser.
+//!
+//! This raises or resolves a concern on a major change proposal, which keeps it from being
+//! accepted while the concern is open.
+//!
+//! The grammar is as follows:
+//!
+//! ```text
+//! Command: `@bot concern <title>`.
+//! Command: `@bot resolve <title>`.
+//!
+//! <title>: the rest of the line, identifying the concern
+//! ```
+
+use crate::error::Error;
+use crate::token::{Token, Tokenizer};
+use std::fmt;
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct ConcernCommand {
+    pub action: ConcernAction,
+    pub title: String,
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum ConcernAction {
+    Raise,
+    Resolve,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum ParseError {
+    NoTitle,
+}
+
+impl std::error::Error for ParseError {}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::NoTitle => write!(f, "no concern title specified"),
+        }
+    }
+}
+
+impl ConcernCommand {
+    pub fn parse<'a>(input: &mut Tokenizer<'a>) -> Result<Option<Self>, Error<'a>> {
+        let mut toks = input.clone();
+        let action = match toks.peek_token()? {
+            Some(Token::Word("concern")) => ConcernAction::Raise,
+            Some(Token::Word("resolve")) => ConcernAction::Resolve,
+            _ => return Ok(None),
+        };
+        toks.next_token()?;
+        let title = toks.rest_of_line();
+        if title.is_empty() {
+            return Err(toks.error(ParseError::NoTitle));
+        }
+        let title = title.to_owned();
+        *input = toks;
+        Ok(Some(ConcernCommand { action, title }))
+    }
+}
+
+#[cfg(test)]
+fn parse<'a>(input: &'a str) -> Result<Option<ConcernCommand>, Error<'a>> {
+    let mut toks = Tokenizer::new(input);
+    Ok(ConcernCommand::parse(&mut toks)?)
+}
+
+#[test]
+fn raise() {
+    assert_eq!(
+        parse("concern needs a crater run\nsecond line"),
+        Ok(Some(ConcernCommand {
+            action: ConcernAction::Raise,
+            title: "needs a crater run".into(),
+        }))
+    );
+}
+
+#[test]
+fn resolve() {
+    assert_eq!(
+        parse("resolve needs a crater run"),
+        Ok(Some(ConcernCommand {
+            action: ConcernAction::Resolve,
+            title: "needs a crater run".into(),
+        }))
+    );
+}
+
+#[test]
+fn no_title() {
+    use std::error::Error;
+    assert_eq!(
+        parse("concern")
+            .unwrap_err()
+            .source()
+            .unwrap()
+            .downcast_ref(),
+        Some(&ParseError::NoTitle),
+    );
+}
+
+#[test]
+fn resolve_no_title() {
+    use std::error::Error;
+    assert_eq!(
+        parse("resolve")
+            .unwrap_err()
+            .source()
+            .unwrap()
+            .downcast_ref(),
+        Some(&ParseError::NoTitle),
+    );
+}
@@ -0,0 +1,168 @@
+//! The final comment period command parser.
+//!
+//! This proposes a final comment period (FCP) for a disposition, and drives it to its end.
+//!
+//! The grammar is as follows:
+//!
+//! ```text
+//! Command: `@bot fcp merge`, `@bot fcp close` or `@bot fcp postpone`.
+//! Command: `@bot fcp cancel`.
+//! Command: `@bot fcp reviewed`.
+//! Command: `@bot fcp concern <title>`.
+//! Command: `@bot fcp resolve <title>`.
+//!
+//! <title>: the rest of the line, identifying the concern
+//! ```
+
+use crate::error::Error;
+use crate::token::{Token, Tokenizer};
+use std::fmt;
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum FcpCommand {
+    /// Propose an FCP with the given disposition.
+    Propose(Disposition),
+    /// Cancel the proposed or running FCP.
+    Cancel,
+    /// Mark the proposal as reviewed by the commenter.
+    Reviewed,
+    /// Raise a concern, which blocks the FCP from starting.
+    Concern(String),
+    /// Resolve a concern.
+    Resolve(String),
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Disposition {
+    Merge,
+    Close,
+    Postpone,
+}
+
+impl fmt::Display for Disposition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Disposition::Merge => write!(f, "merge"),
+            Disposition::Close => write!(f, "close"),
+            Disposition::Postpone => write!(f, "postpone"),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum ParseError {
+    ExpectedEnd,
+    NoAction,
+    NoTitle,
+}
+
+impl std::error::Error for ParseError {}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::ExpectedEnd => write!(f, "expected end of command"),
+            ParseError::NoAction => write!(
+                f,
+                "expected one of `merge`, `close`, `postpone`, `cancel`, `reviewed`, \
+                 `concern` or `resolve`"
+            ),
+            ParseError::NoTitle => write!(f, "no concern title specified"),
+        }
+    }
+}
+
+impl FcpCommand {
+    pub fn parse<'a>(input: &mut Tokenizer<'a>) -> Result<Option<Self>, Error<'a>> {
+        let mut toks = input.clone();
+        if let Some(Token::Word("fcp")) = toks.peek_token()? {
+            toks.next_token()?;
+        } else {
+            return Ok(None);
+        }
+        let cmd = match toks.next_token()? {
+            Some(Token::Word("merge")) => FcpCommand::Propose(Disposition::Merge),
+            Some(Token::Word("close")) => FcpCommand::Propose(Disposition::Close),
+            Some(Token::Word("postpone")) => FcpCommand::Propose(Disposition::Postpone),
+            Some(Token::Word("cancel")) => FcpCommand::Cancel,
+            Some(Token::Word("reviewed")) => FcpCommand::Reviewed,
+            Some(Token::Word(action @ "concern")) | Some(Token::Word(action @ "resolve")) => {
+                let title = toks.rest_of_line();
+                if title.is_empty() {
+                    return Err(toks.error(ParseError::NoTitle));
+                }
+                let title = title.to_owned();
+                *input = toks;
+                return Ok(Some(if action == "concern" {
+                    FcpCommand::Concern(title)
+                } else {
+                    FcpCommand::Resolve(title)
+                }));
+            }
+            _ => return Err(toks.error(ParseError::NoAction)),
+        };
+        if let Some(Token::Dot) | Some(Token::EndOfLine) = toks.peek_token()? {
+            toks.next_token()?;
+            *input = toks;
+            Ok(Some(cmd))
+        } else {
+            Err(toks.error(ParseError::ExpectedEnd))
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse<'a>(input: &'a str) -> Result<Option<FcpCommand>, Error<'a>> {
+    let mut toks = Tokenizer::new(input);
+    Ok(FcpCommand::parse(&mut toks)?)
+}
+
+#[test]
+fn propose() {
+    assert_eq!(
+        parse("fcp merge."),
+        Ok(Some(FcpCommand::Propose(Disposition::Merge)))
+    );
+    assert_eq!(
+        parse("fcp postpone"),
+        Ok(Some(FcpCommand::Propose(Disposition::Postpone)))
+    );
+}
+
+#[test]
+fn concern() {
+    assert_eq!(
+        parse("fcp concern what about editions?"),
+        Ok(Some(FcpCommand::Concern("what about editions?".into())))
+    );
+    assert_eq!(
+        parse("fcp resolve what about editions?"),
+        Ok(Some(FcpCommand::Resolve("what about editions?".into())))
+    );
+}
+
+#[test]
+fn no_action() {
+    use std::error::Error;
+    assert_eq!(
+        parse("fcp merge-it")
+            .unwrap_err()
+            .source()
+            .unwrap()
+            .downcast_ref(),
+        Some(&ParseError::NoAction),
+    );
+}
+
+#[test]
+fn expected_end() {
+    use std::error::Error;
+    assert_eq!(
+        parse("fcp cancel now")
+            .unwrap_err()
+            .source()
+            .unwrap()
+            .downcast_ref(),
+        Some(&ParseError::ExpectedEnd),
+    );
+}
@@ -1,10 +1,12 @@
 //! The glacier command parser.
 //!
-//! This adds the option to track ICEs. The <code-source> must be in quotes.
+//! This adds the option to track ICEs. The <code-source> must be in quotes; without it, the first
+//! fenced Rust code block of the comment (or of the issue description) is used.
 //!
 //! The grammar is as follows:
 //!
 //! ```text
+//! Command: `@bot glacier`
 //! Command: `@bot glacier <code-source>`
 //!
 //! <code-source>: any URL that resolves to plain-text Rust code
@@ -16,12 +18,12 @@ use std::fmt;
 
 #[derive(PartialEq, Eq, Debug)]
 pub struct GlacierCommand {
-    pub source: String,
+    /// The URL of the code, if it is not given in a code block.
+    pub source: Option<String>,
 }
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum ParseError {
-    NoLink,
     InvalidLink,
 }
 
@@ -30,7 +32,6 @@ impl std::error::Error for ParseError {}
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::NoLink => write!(f, "no link provided - did you forget the quotes around it?"),
             Self::InvalidLink => write!(f, "invalid link - must be from a playground gist"),
         }
     }
@@ -45,7 +46,10 @@ impl GlacierCommand {
                 Some(Token::Quote(s)) => {
                     let source = s.to_owned();
                     if source.starts_with("https://gist.github.com/") {
-                        return Ok(Some(GlacierCommand { source }));
+                        *input = toks;
+                        return Ok(Some(GlacierCommand {
+                            source: Some(source),
+                        }));
                     } else {
                         return Err(toks.error(ParseError::InvalidLink));
                     }
@@ -54,7 +58,8 @@ impl GlacierCommand {
                     return Err(toks.error(ParseError::InvalidLink));
                 }
                 _ => {
-                    return Err(toks.error(ParseError::NoLink));
+                    *input = toks;
+                    return Ok(Some(GlacierCommand { source: None }));
                 }
             }
         } else {
@@ -73,16 +78,8 @@ mod test {
     }
 
     #[test]
-    fn glacier_empty() {
-        use std::error::Error;
-        assert_eq!(
-            parse("glacier")
-                .unwrap_err()
-                .source()
-                .unwrap()
-                .downcast_ref(),
-            Some(&ParseError::NoLink),
-        );
+    fn glacier_code_block() {
+        assert_eq!(parse("glacier"), Ok(Some(GlacierCommand { source: None })));
     }
 
     #[test]
@@ -105,7 +102,9 @@ mod test {
                 r#"glacier "https://gist.github.com/rust-play/89d6c8a2398dd2dd5fcb7ef3e8109c7b""#
             ),
             Ok(Some(GlacierCommand {
-                source: "https://gist.github.com/rust-play/89d6c8a2398dd2dd5fcb7ef3e8109c7b".into()
+                source: Some(
+                    "https://gist.github.com/rust-play/89d6c8a2398dd2dd5fcb7ef3e8109c7b".into()
+                )
             }))
         );
     }
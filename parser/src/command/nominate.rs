@@ -8,8 +8,17 @@
 //! `@bot nominate <team>`.
 //! `@bot beta-accept`.
 //! `@bot beta-approve`.
+//! `@bot stable-nominate <team>`.
+//! `@bot stable-accept`.
+//! `@bot stable-approve`.
+//! `@bot resolve-nomination <team>: <summary>`.
 //! ```
 //!
+//! Nominations can be followed by a reason after a `:`, which extends to the end of the line:
+//! `@bot nominate lang: should this be allowed in const fn?`. The summary of a resolved nomination
+//! is given the same way, and is required. A `.` ends the command, so a sentence following it is
+//! not part of the command.
+//!
 //! This constrains to just one team; users should issue the command multiple
 //! times if they want to nominate for more than one team. This is to encourage
 //! descriptions of what to do targeted at each team, rather than a general
@@ -23,19 +32,25 @@ use std::fmt;
 pub struct NominateCommand {
     pub team: String,
     pub style: Style,
+    /// The reason for the nomination, or the summary of its resolution.
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum Style {
     Beta,
     BetaApprove,
+    Stable,
+    StableApprove,
     Decision,
+    Resolve,
 }
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum ParseError {
     ExpectedEnd,
     NoTeam,
+    NoSummary,
 }
 
 impl std::error::Error for ParseError {}
@@ -45,6 +60,7 @@ impl fmt::Display for ParseError {
         match self {
             ParseError::ExpectedEnd => write!(f, "expected end of command"),
             ParseError::NoTeam => write!(f, "no team specified"),
+            ParseError::NoSummary => write!(f, "no summary of the resolution given"),
         }
     }
 }
@@ -57,10 +73,14 @@ impl NominateCommand {
             Some(Token::Word("nominate")) => Style::Decision,
             Some(Token::Word("beta-accept")) => Style::BetaApprove,
             Some(Token::Word("beta-approve")) => Style::BetaApprove,
+            Some(Token::Word("stable-nominate")) => Style::Stable,
+            Some(Token::Word("stable-accept")) => Style::StableApprove,
+            Some(Token::Word("stable-approve")) => Style::StableApprove,
+            Some(Token::Word("resolve-nomination")) => Style::Resolve,
             None | Some(_) => return Ok(None),
         };
         toks.next_token()?;
-        let team = if style != Style::BetaApprove {
+        let team = if style != Style::BetaApprove && style != Style::StableApprove {
             if let Some(Token::Word(team)) = toks.next_token()? {
                 team.to_owned()
             } else {
@@ -69,13 +89,28 @@ impl NominateCommand {
         } else {
             String::new()
         };
-        if let Some(Token::Dot) | Some(Token::EndOfLine) = toks.peek_token()? {
-            toks.next_token()?;
-            *input = toks;
-            return Ok(Some(NominateCommand { team, style }));
-        } else {
-            return Err(toks.error(ParseError::ExpectedEnd));
+        let reason = match toks.peek_token()? {
+            Some(Token::Colon) => {
+                toks.next_token()?;
+                Some(toks.rest_of_line())
+                    .filter(|reason| !reason.is_empty())
+                    .map(|reason| reason.to_owned())
+            }
+            Some(Token::Dot) | Some(Token::EndOfLine) => {
+                toks.next_token()?;
+                None
+            }
+            _ => return Err(toks.error(ParseError::ExpectedEnd)),
+        };
+        if style == Style::Resolve && reason.is_none() {
+            return Err(toks.error(ParseError::NoSummary));
         }
+        *input = toks;
+        return Ok(Some(NominateCommand {
+            team,
+            style,
+            reason,
+        }));
     }
 }
 
@@ -92,6 +127,7 @@ fn test_1() {
         Ok(Some(NominateCommand {
             team: "compiler".into(),
             style: Style::Decision,
+            reason: None,
         }))
     );
 }
@@ -103,6 +139,7 @@ fn test_2() {
         Ok(Some(NominateCommand {
             team: "compiler".into(),
             style: Style::Beta,
+            reason: None,
         }))
     );
 }
@@ -132,3 +169,74 @@ fn test_4() {
         Some(&ParseError::NoTeam),
     );
 }
+
+#[test]
+fn reason() {
+    assert_eq!(
+        parse("nominate lang: should this be allowed in const fn?\nmore text"),
+        Ok(Some(NominateCommand {
+            team: "lang".into(),
+            style: Style::Decision,
+            reason: Some("should this be allowed in const fn?".into()),
+        }))
+    );
+}
+
+#[test]
+fn sentence_after_dot() {
+    let mut toks = Tokenizer::new("beta-nominate compiler. Fixes a regression.");
+    assert_eq!(
+        NominateCommand::parse(&mut toks),
+        Ok(Some(NominateCommand {
+            team: "compiler".into(),
+            style: Style::Beta,
+            reason: None,
+        }))
+    );
+    assert_eq!(toks.next_token(), Ok(Some(Token::Word("Fixes"))));
+}
+
+#[test]
+fn resolve() {
+    assert_eq!(
+        parse("resolve-nomination lang: discussed, we want an RFC first"),
+        Ok(Some(NominateCommand {
+            team: "lang".into(),
+            style: Style::Resolve,
+            reason: Some("discussed, we want an RFC first".into()),
+        }))
+    );
+}
+
+#[test]
+fn resolve_no_summary() {
+    use std::error::Error;
+    assert_eq!(
+        parse("resolve-nomination lang.")
+            .unwrap_err()
+            .source()
+            .unwrap()
+            .downcast_ref(),
+        Some(&ParseError::NoSummary),
+    );
+}
+
+#[test]
+fn stable() {
+    assert_eq!(
+        parse("stable-nominate compiler"),
+        Ok(Some(NominateCommand {
+            team: "compiler".into(),
+            style: Style::Stable,
+            reason: None,
+        }))
+    );
+    assert_eq!(
+        parse("stable-accept."),
+        Ok(Some(NominateCommand {
+            team: String::new(),
+            style: Style::StableApprove,
+            reason: None,
+        }))
+    );
+}
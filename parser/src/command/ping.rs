@@ -6,6 +6,8 @@
 //!
 //! ```text
 //! Command: `@bot ping <team>`.
+//! Command: `@bot ping subscribe <team>`.
+//! Command: `@bot ping unsubscribe <team>`.
 //! ```
 
 use crate::error::Error;
@@ -14,9 +16,20 @@ use std::fmt;
 
 #[derive(PartialEq, Eq, Debug)]
 pub struct PingCommand {
+    pub action: PingAction,
     pub team: String,
 }
 
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum PingAction {
+    /// Ping the members of the team.
+    Ping,
+    /// Be pinged whenever the team is pinged.
+    Subscribe,
+    /// Stop being pinged when the team is pinged.
+    Unsubscribe,
+}
+
 #[derive(PartialEq, Eq, Debug)]
 pub enum ParseError {
     ExpectedEnd,
@@ -39,6 +52,14 @@ impl PingCommand {
         let mut toks = input.clone();
         if let Some(Token::Word("ping")) = toks.peek_token()? {
             toks.next_token()?;
+            let action = match toks.peek_token()? {
+                Some(Token::Word("subscribe")) => PingAction::Subscribe,
+                Some(Token::Word("unsubscribe")) => PingAction::Unsubscribe,
+                _ => PingAction::Ping,
+            };
+            if action != PingAction::Ping {
+                toks.next_token()?;
+            }
             let team = if let Some(Token::Word(team)) = toks.next_token()? {
                 team.to_owned()
             } else {
@@ -47,7 +68,7 @@ impl PingCommand {
             if let Some(Token::Dot) | Some(Token::EndOfLine) = toks.peek_token()? {
                 toks.next_token()?;
                 *input = toks;
-                return Ok(Some(PingCommand { team }));
+                return Ok(Some(PingCommand { action, team }));
             } else {
                 return Err(toks.error(ParseError::ExpectedEnd));
             }
@@ -68,11 +89,43 @@ fn test_1() {
     assert_eq!(
         parse("ping LLVM-icebreakers."),
         Ok(Some(PingCommand {
+            action: PingAction::Ping,
             team: "LLVM-icebreakers".into()
         }))
     );
 }
 
+#[test]
+fn subscribe() {
+    assert_eq!(
+        parse("ping subscribe LLVM-icebreakers."),
+        Ok(Some(PingCommand {
+            action: PingAction::Subscribe,
+            team: "LLVM-icebreakers".into()
+        }))
+    );
+    assert_eq!(
+        parse("ping unsubscribe LLVM-icebreakers"),
+        Ok(Some(PingCommand {
+            action: PingAction::Unsubscribe,
+            team: "LLVM-icebreakers".into()
+        }))
+    );
+}
+
+#[test]
+fn subscribe_no_team() {
+    use std::error::Error;
+    assert_eq!(
+        parse("ping subscribe")
+            .unwrap_err()
+            .source()
+            .unwrap()
+            .downcast_ref(),
+        Some(&ParseError::NoTeam),
+    );
+}
+
 #[test]
 fn test_2() {
     use std::error::Error;
@@ -1,15 +1,91 @@
-#[derive(PartialEq, Eq, Debug)]
-pub struct PrioritizeCommand;
+//! The prioritize command parser.
+//!
+//! Without a priority, this requests that the issue be prioritized; with one, the issue is given
+//! that priority label.
+//!
+//! The grammar is as follows:
+//!
+//! ```text
+//! Command: `@bot prioritize`.
+//! Command: `@bot prioritize <priority>`.
+//!
+//! <priority>: a priority label, e.g. `P-high`
+//! ```
+//!
+//! Only words shaped like a label (`<prefix>-<name>`) are taken as a priority, so that the
+//! command can be followed by a sentence. The handler then checks the configured prefix.
 
 use crate::error::Error;
 use crate::token::{Token, Tokenizer};
 
+#[derive(PartialEq, Eq, Debug)]
+pub struct PrioritizeCommand {
+    pub priority: Option<String>,
+}
+
 impl PrioritizeCommand {
     pub fn parse<'a>(input: &mut Tokenizer<'a>) -> Result<Option<Self>, Error<'a>> {
-        if let Some(Token::Word("prioritize")) = input.peek_token()? {
-            Ok(Some(Self))
+        let mut toks = input.clone();
+        if let Some(Token::Word("prioritize")) = toks.peek_token()? {
+            toks.next_token()?;
+            let priority = match toks.peek_token()? {
+                Some(Token::Word(priority)) if is_label_like(priority) => {
+                    toks.next_token()?;
+                    Some(priority.to_owned())
+                }
+                _ => None,
+            };
+            if let Some(Token::Dot) | Some(Token::EndOfLine) = toks.peek_token()? {
+                toks.next_token()?;
+            }
+            *input = toks;
+            Ok(Some(Self { priority }))
         } else {
             Ok(None)
         }
     }
 }
+
+/// Whether `word` looks like a prefixed label, e.g. `P-high`.
+fn is_label_like(word: &str) -> bool {
+    match word.find('-') {
+        Some(dash) => dash > 0 && dash + 1 < word.len(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+fn parse<'a>(input: &'a str) -> Result<Option<PrioritizeCommand>, Error<'a>> {
+    let mut toks = Tokenizer::new(input);
+    Ok(PrioritizeCommand::parse(&mut toks)?)
+}
+
+#[test]
+fn request() {
+    assert_eq!(
+        parse("prioritize."),
+        Ok(Some(PrioritizeCommand { priority: None }))
+    );
+}
+
+#[test]
+fn priority() {
+    assert_eq!(
+        parse("prioritize P-high"),
+        Ok(Some(PrioritizeCommand {
+            priority: Some("P-high".into())
+        }))
+    );
+}
+
+#[test]
+fn sentence() {
+    assert_eq!(
+        parse("prioritize please"),
+        Ok(Some(PrioritizeCommand { priority: None }))
+    );
+    assert_eq!(
+        parse("prioritize - it's urgent"),
+        Ok(Some(PrioritizeCommand { priority: None }))
+    );
+}
@@ -5,7 +5,21 @@
 //! The grammar is as follows:
 //!
 //! ```text
-//! Command: `@bot modify labels:? to? <label-list>.`
+//! Command:
+//! `@bot modify labels <issue-list>? :? to? <label-list>.`
+//! `@bot label <issue-list>? :? to? <label-list>.`
+//!
+//! <issue-list>:
+//!  - <issue>
+//!  - <issue> <issue-list>
+//!  - <issue>, <issue-list>
+//!
+//! <issue>:
+//!  - #<number>
+//!  - <repository>#<number>
+//!  - <owner>/<repository>#<number>
+//!
+//! Without an <issue-list>, the command applies to the issue it was posted on.
 //!
 //! <label-list>:
 //!  - <label-delta>
@@ -30,7 +44,21 @@ use std::error::Error as _;
 use std::fmt;
 
 #[derive(Debug, PartialEq, Eq)]
-pub struct RelabelCommand(pub Vec<LabelDelta>);
+pub struct RelabelCommand {
+    /// The issues to relabel; empty means the issue the command was posted on.
+    pub targets: Vec<IssueTarget>,
+    pub deltas: Vec<LabelDelta>,
+}
+
+/// A reference to an issue, possibly in another repository.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IssueTarget {
+    /// `None` if the organization is the same as the current issue's.
+    pub organization: Option<String>,
+    /// `None` if the repository is the same as the current issue's.
+    pub repository: Option<String>,
+    pub number: u64,
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum LabelDelta {
@@ -62,6 +90,48 @@ impl fmt::Display for ParseError {
     }
 }
 
+impl IssueTarget {
+    fn parse(input: &str) -> Option<IssueTarget> {
+        fn is_name(s: &str) -> bool {
+            !s.is_empty()
+                && s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        }
+
+        let hash = input.rfind('#')?;
+        let (path, number) = (&input[..hash], &input[hash + 1..]);
+        if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let number = number.parse().ok()?;
+        let (organization, repository) = match path.find('/') {
+            _ if path.is_empty() => (None, None),
+            None if is_name(path) => (None, Some(path)),
+            Some(slash) if is_name(&path[..slash]) && is_name(&path[slash + 1..]) => {
+                (Some(&path[..slash]), Some(&path[slash + 1..]))
+            }
+            _ => return None,
+        };
+        Some(IssueTarget {
+            organization: organization.map(|o| o.to_owned()),
+            repository: repository.map(|r| r.to_owned()),
+            number,
+        })
+    }
+}
+
+impl fmt::Display for IssueTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(organization) = &self.organization {
+            write!(f, "{}/", organization)?;
+        }
+        if let Some(repository) = &self.repository {
+            write!(f, "{}", repository)?;
+        }
+        write!(f, "#{}", self.number)
+    }
+}
+
 impl Label {
     fn parse(input: &str) -> Result<Label, ParseError> {
         if input.is_empty() {
@@ -127,15 +197,31 @@ fn delta_empty() {
 impl RelabelCommand {
     pub fn parse<'a>(input: &mut Tokenizer<'a>) -> Result<Option<Self>, Error<'a>> {
         let mut toks = input.clone();
-        if let Some(Token::Word("modify")) = toks.next_token()? {
-            // continue
-        } else {
-            return Ok(None);
+        match toks.next_token()? {
+            Some(Token::Word("modify")) => {
+                if let Some(Token::Word("labels")) = toks.next_token()? {
+                    // continue
+                } else {
+                    return Ok(None);
+                }
+            }
+            Some(Token::Word("label")) => {}
+            _ => return Ok(None),
         }
-        if let Some(Token::Word("labels")) = toks.next_token()? {
-            // continue
-        } else {
-            return Ok(None);
+        let mut targets = Vec::new();
+        loop {
+            // Repository names can contain dots, which end words.
+            let mut after = toks.clone();
+            match IssueTarget::parse(after.next_raw_word()) {
+                Some(target) => {
+                    toks = after;
+                    targets.push(target);
+                    if let Some(Token::Comma) = toks.peek_token()? {
+                        toks.next_token()?;
+                    }
+                }
+                None => break,
+            }
         }
         if let Some(Token::Colon) = toks.peek_token()? {
             toks.next_token()?;
@@ -171,7 +257,7 @@ impl RelabelCommand {
             {
                 toks.next_token()?;
                 *input = toks;
-                return Ok(Some(RelabelCommand(deltas)));
+                return Ok(Some(RelabelCommand { targets, deltas }));
             }
         }
     }
@@ -180,7 +266,13 @@ impl RelabelCommand {
 #[cfg(test)]
 fn parse<'a>(input: &'a str) -> Result<Option<Vec<LabelDelta>>, Error<'a>> {
     let mut toks = Tokenizer::new(input);
-    Ok(RelabelCommand::parse(&mut toks)?.map(|c| c.0))
+    Ok(RelabelCommand::parse(&mut toks)?.map(|c| c.deltas))
+}
+
+#[cfg(test)]
+fn parse_targets<'a>(input: &'a str) -> Result<Option<Vec<IssueTarget>>, Error<'a>> {
+    let mut toks = Tokenizer::new(input);
+    Ok(RelabelCommand::parse(&mut toks)?.map(|c| c.targets))
 }
 
 #[test]
@@ -238,3 +330,88 @@ fn parse_to_colon() {
         ]))
     );
 }
+
+#[test]
+fn parse_label_shorthand() {
+    assert_eq!(
+        parse("label +T-compiler -T-lang"),
+        Ok(Some(vec![
+            LabelDelta::Add(Label("T-compiler".into())),
+            LabelDelta::Remove(Label("T-lang".into())),
+        ]))
+    );
+    assert_eq!(parse_targets("label: +T-compiler"), Ok(Some(vec![])));
+}
+
+#[test]
+fn parse_targets_simple() {
+    assert_eq!(
+        parse_targets("label rust-lang/rust#123 rust-lang/rust#456 +T-lang -needs-triage"),
+        Ok(Some(vec![
+            IssueTarget {
+                organization: Some("rust-lang".into()),
+                repository: Some("rust".into()),
+                number: 123,
+            },
+            IssueTarget {
+                organization: Some("rust-lang".into()),
+                repository: Some("rust".into()),
+                number: 456,
+            },
+        ]))
+    );
+    assert_eq!(
+        parse("label rust-lang/rust#123 rust-lang/rust#456 +T-lang -needs-triage"),
+        Ok(Some(vec![
+            LabelDelta::Add(Label("T-lang".into())),
+            LabelDelta::Remove(Label("needs-triage".into())),
+        ]))
+    );
+}
+
+#[test]
+fn parse_targets_short_forms() {
+    assert_eq!(
+        parse_targets("modify labels #1, cargo#2 to: +T-cargo"),
+        Ok(Some(vec![
+            IssueTarget {
+                organization: None,
+                repository: None,
+                number: 1,
+            },
+            IssueTarget {
+                organization: None,
+                repository: Some("cargo".into()),
+                number: 2,
+            },
+        ]))
+    );
+}
+
+#[test]
+fn parse_targets_with_dots() {
+    assert_eq!(
+        parse_targets("label rust-lang/foo.rs#1: +T-lang."),
+        Ok(Some(vec![IssueTarget {
+            organization: Some("rust-lang".into()),
+            repository: Some("foo.rs".into()),
+            number: 1,
+        }]))
+    );
+    assert_eq!(
+        parse("label rust-lang/foo.rs#1: +T-lang."),
+        Ok(Some(vec![LabelDelta::Add(Label("T-lang".into()))]))
+    );
+}
+
+#[test]
+fn parse_targets_not_issues() {
+    assert_eq!(IssueTarget::parse("+T-lang"), None);
+    assert_eq!(IssueTarget::parse("rust#"), None);
+    assert_eq!(IssueTarget::parse("a/b/c#1"), None);
+    assert_eq!(IssueTarget::parse("rust#1a"), None);
+    assert_eq!(
+        IssueTarget::parse("rust-lang/rust#1").unwrap().to_string(),
+        "rust-lang/rust#1"
+    );
+}
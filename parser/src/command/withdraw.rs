@@ -0,0 +1,43 @@
+//! The withdraw command parser.
+//!
+//! This withdraws a major change proposal, closing its issue.
+//!
+//! The grammar is as follows:
+//!
+//! ```text
+//! Command: `@bot withdraw`.
+//! ```
+
+use crate::error::Error;
+use crate::token::{Token, Tokenizer};
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct WithdrawCommand;
+
+impl WithdrawCommand {
+    pub fn parse<'a>(input: &mut Tokenizer<'a>) -> Result<Option<Self>, Error<'a>> {
+        if let Some(Token::Word("withdraw")) = input.peek_token()? {
+            Ok(Some(Self))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse<'a>(input: &'a str) -> Result<Option<WithdrawCommand>, Error<'a>> {
+    let mut toks = Tokenizer::new(input);
+    Ok(WithdrawCommand::parse(&mut toks)?)
+}
+
+#[test]
+fn withdraw() {
+    assert_eq!(parse("withdraw"), Ok(Some(WithdrawCommand)));
+    assert_eq!(parse("withdraw."), Ok(Some(WithdrawCommand)));
+}
+
+#[test]
+fn other_words() {
+    assert_eq!(parse("withdrawn"), Ok(None));
+    assert_eq!(parse("second"), Ok(None));
+}
@@ -1,4 +1,4 @@
-mod code_block;
+pub mod code_block;
 pub mod command;
 pub mod error;
 mod mentions;
@@ -169,6 +169,29 @@ impl<'a> Tokenizer<'a> {
         self.cur_pos()
     }
 
+    /// Consumes the rest of the current line, returning it without surrounding whitespace.
+    pub fn rest_of_line(&mut self) -> &'a str {
+        self.consume_whitespace();
+        let start = self.cur_pos();
+        while self.cur().map_or(false, |(_, ch)| ch != '\n') {
+            self.advance();
+        }
+        self.str_from(start).trim_end()
+    }
+
+    /// Consumes the next word as written, up to whitespace, `,`, `:` or `;`. Unlike a
+    /// `Token::Word`, it is not split at dots, e.g. in `rust-lang/foo.rs#1`.
+    pub fn next_raw_word(&mut self) -> &'a str {
+        self.consume_whitespace();
+        let start = self.cur_pos();
+        while self.cur().map_or(false, |(_, ch)| {
+            !ch.is_whitespace() && ch != ',' && ch != ':' && ch != ';'
+        }) {
+            self.advance();
+        }
+        self.str_from(start)
+    }
+
     pub fn peek_token(&mut self) -> Result<Option<Token<'a>>, Error<'a>> {
         self.clone().next_token()
     }
@@ -339,3 +362,12 @@ fn tokenize_raw_string_prohibit_1() {
         (18, ErrorKind::QuoteInWord)
     );
 }
+
+#[test]
+fn tokenize_raw_word() {
+    let mut toks = Tokenizer::new(" rust-lang/foo.rs#1, bar.");
+    assert_eq!(toks.next_raw_word(), "rust-lang/foo.rs#1");
+    assert_eq!(toks.next_token().unwrap(), Some(Token::Comma));
+    assert_eq!(toks.next_raw_word(), "bar.");
+    assert_eq!(toks.next_raw_word(), "");
+}
@@ -5,6 +5,7 @@ use async_trait::async_trait;
 use reqwest::Client;
 use tera::{Context, Tera};
 
+use crate::db::nominations::{open_nominations, Nomination};
 use crate::github::{self, GithubClient, Repository};
 
 #[async_trait]
@@ -35,6 +36,25 @@ pub struct IssueDecorator {
     pub repo_name: String,
     pub labels: String,
     pub assignees: String,
+    /// Unresolved nominations, with their reasons.
+    pub nominations: Vec<NominationDecorator>,
+}
+
+#[derive(serde::Serialize)]
+pub struct NominationDecorator {
+    pub team: String,
+    pub nominator: String,
+    pub reason: Option<String>,
+}
+
+impl From<&Nomination> for NominationDecorator {
+    fn from(nomination: &Nomination) -> NominationDecorator {
+        NominationDecorator {
+            team: nomination.team.clone(),
+            nominator: nomination.nominator.clone(),
+            reason: nomination.reason.clone(),
+        }
+    }
 }
 
 lazy_static! {
@@ -53,6 +73,18 @@ lazy_static! {
 impl<'a> Action for Step<'a> {
     async fn call(&self) -> String {
         let gh = GithubClient::new_with_default_token(Client::new());
+        // Nomination reasons are only available with a database.
+        let db = if std::env::var("DATABASE_URL").is_ok() {
+            match crate::db::make_client().await {
+                Ok(db) => Some(db),
+                Err(err) => {
+                    eprintln!("WARNING: not showing nomination reasons: {:?}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
 
         let mut context = Context::new();
         let mut results = HashMap::new();
@@ -61,6 +93,16 @@ impl<'a> Action for Step<'a> {
             let repository = Repository {
                 full_name: repo.to_string(),
             };
+            let nominations = match &db {
+                Some(db) => match open_nominations(db, repo).await {
+                    Ok(nominations) => nominations,
+                    Err(err) => {
+                        eprintln!("WARNING: not showing nomination reasons: {:?}", err);
+                        Vec::new()
+                    }
+                },
+                None => Vec::new(),
+            };
 
             for QueryMap { name, query } in queries {
                 match query.kind {
@@ -93,6 +135,11 @@ impl<'a> Action for Step<'a> {
                                             .map(|u| u.login.as_ref())
                                             .collect::<Vec<_>>()
                                             .join(", "),
+                                        nominations: nominations
+                                            .iter()
+                                            .filter(|n| n.issue_number == issue.number)
+                                            .map(NominationDecorator::from)
+                                            .collect(),
                                     })
                                     .collect();
 
@@ -0,0 +1,71 @@
+use crate::db::backports::pending_backports;
+use crate::db::DbClient;
+use crate::escape_html;
+
+pub async fn render(db: &DbClient, repo: &str) -> String {
+    let backports = match pending_backports(db, repo).await {
+        Ok(b) => b,
+        Err(e) => {
+            return format!("{:?}", e.context("getting backports"));
+        }
+    };
+
+    let mut out = String::new();
+    out.push_str("<html>");
+    out.push_str("<head>");
+    out.push_str("<meta charset=\"utf-8\">");
+    out.push_str("<title>Triagebot Backport Status</title>");
+    out.push_str("</head>");
+    out.push_str("<body>");
+
+    out.push_str(&format!(
+        "<h3>Pending backports in {}</h3>",
+        escape_html(repo)
+    ));
+
+    if backports.is_empty() {
+        out.push_str("<p><em>There are no pending backports.</em></p>");
+    } else {
+        out.push_str("<table>");
+        out.push_str(
+            "<tr><th>PR</th><th>Channel</th><th>State</th><th>Backport PR</th>\
+            <th>Last update</th></tr>",
+        );
+        for backport in backports {
+            let pr_link = |number: u64| {
+                format!(
+                    "<a href='https://github.com/{}/pull/{}'>#{}</a>",
+                    escape_html(repo),
+                    number,
+                    number
+                )
+            };
+            out.push_str("<tr>");
+            out.push_str(&format!("<td>{}</td>", pr_link(backport.pr_number)));
+            out.push_str(&format!("<td>{}</td>", escape_html(&backport.channel)));
+            match &backport.error {
+                Some(error) => out.push_str(&format!(
+                    "<td>{} ({})</td>",
+                    escape_html(&backport.state),
+                    escape_html(error)
+                )),
+                None => out.push_str(&format!("<td>{}</td>", escape_html(&backport.state))),
+            }
+            out.push_str(&format!(
+                "<td>{}</td>",
+                backport.backport_pr.map(pr_link).unwrap_or_default()
+            ));
+            out.push_str(&format!(
+                "<td>{}</td>",
+                backport.updated_at.format("%Y-%m-%d %H:%M %:z")
+            ));
+            out.push_str("</tr>");
+        }
+        out.push_str("</table>");
+    }
+
+    out.push_str("</body>");
+    out.push_str("</html>");
+
+    out
+}
@@ -0,0 +1,169 @@
+//! The chat services triagebot posts to.
+//!
+//! A channel is configured in `triagebot.toml` with the name of its backend, e.g.
+//! `channel = { backend = "zulip", stream = 245100 }` or
+//! `channel = { backend = "slack", webhook = "compiler" }`. The URLs of incoming webhooks are
+//! secrets, so the configuration only names them: the `compiler` webhook is read from the
+//! `CHAT_WEBHOOK_COMPILER` environment variable.
+
+use crate::config::ChannelConfig;
+use crate::handlers::Context;
+use crate::zulip::{AddReaction, MessageApiRequest, Recipient, SentMessage, ZulipClient};
+use anyhow::Context as _;
+use async_trait::async_trait;
+use std::env;
+
+/// A channel of a chat service.
+#[async_trait]
+pub trait ChatBackend: Send + Sync {
+    /// Sends a message to the channel, in `topic` if the service has topics, returning the ID of
+    /// the message if the service has IDs.
+    async fn send(&self, topic: Option<&str>, content: &str) -> anyhow::Result<Option<u64>>;
+
+    /// Sends a direct message to `user`, identified as the service identifies users.
+    async fn direct_message(&self, user: &str, content: &str) -> anyhow::Result<()>;
+
+    /// Reacts to the message with ID `message_id` with `emoji`.
+    async fn add_reaction(&self, message_id: u64, emoji: &str) -> anyhow::Result<()>;
+
+    /// A link to the channel, or to `topic` in it.
+    fn permalink(&self, topic: Option<&str>) -> Option<String>;
+}
+
+/// Returns the backend of a configured channel.
+pub(crate) fn backend<'a>(
+    config: &ChannelConfig,
+    ctx: &'a Context,
+) -> anyhow::Result<Box<dyn ChatBackend + 'a>> {
+    let client = ctx.github.raw();
+    Ok(match config {
+        ChannelConfig::Zulip { stream } => Box::new(ZulipStream::new(&ctx.zulip, *stream)),
+        ChannelConfig::Slack { webhook } => {
+            Box::new(Webhook::new(client, WebhookKind::Slack, webhook)?)
+        }
+        ChannelConfig::Discord { webhook } => {
+            Box::new(Webhook::new(client, WebhookKind::Discord, webhook)?)
+        }
+    })
+}
+
+/// A Zulip stream.
+pub struct ZulipStream<'a> {
+    zulip: &'a ZulipClient,
+    stream: u64,
+}
+
+impl<'a> ZulipStream<'a> {
+    pub fn new(zulip: &'a ZulipClient, stream: u64) -> Self {
+        ZulipStream { zulip, stream }
+    }
+}
+
+#[async_trait]
+impl ChatBackend for ZulipStream<'_> {
+    async fn send(&self, topic: Option<&str>, content: &str) -> anyhow::Result<Option<u64>> {
+        let req = MessageApiRequest {
+            recipient: Recipient::Stream {
+                id: self.stream,
+                topic: topic.unwrap_or("(no topic)"),
+            },
+            content,
+        };
+        let body = req
+            .send(self.zulip)
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let sent = serde_json::from_str::<SentMessage>(&body)
+            .with_context(|| format!("{:?} did not deserialize as SentMessage", body))?;
+        Ok(Some(sent.id))
+    }
+
+    async fn direct_message(&self, user: &str, content: &str) -> anyhow::Result<()> {
+        self.zulip
+            .direct_message(Recipient::Email { email: user }, content)
+            .await
+    }
+
+    async fn add_reaction(&self, message_id: u64, emoji: &str) -> anyhow::Result<()> {
+        AddReaction {
+            message_id,
+            emoji_name: emoji,
+        }
+        .send(self.zulip)
+        .await?
+        .error_for_status()?;
+        Ok(())
+    }
+
+    fn permalink(&self, topic: Option<&str>) -> Option<String> {
+        Some(match topic {
+            Some(topic) => Recipient::Stream {
+                id: self.stream,
+                topic,
+            }
+            .url(self.zulip),
+            None => format!("{}/#narrow/stream/{}-xxx", self.zulip.realm(), self.stream),
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum WebhookKind {
+    Slack,
+    Discord,
+}
+
+/// An incoming webhook, which can only post to the channel it was created for.
+struct Webhook<'a> {
+    client: &'a reqwest::Client,
+    kind: WebhookKind,
+    url: String,
+}
+
+impl<'a> Webhook<'a> {
+    fn new(client: &'a reqwest::Client, kind: WebhookKind, name: &str) -> anyhow::Result<Self> {
+        let var = format!(
+            "CHAT_WEBHOOK_{}",
+            name.to_ascii_uppercase().replace('-', "_")
+        );
+        let url = env::var(&var).with_context(|| format!("webhook `{}` needs {}", name, var))?;
+        Ok(Webhook { client, kind, url })
+    }
+}
+
+#[async_trait]
+impl ChatBackend for Webhook<'_> {
+    async fn send(&self, topic: Option<&str>, content: &str) -> anyhow::Result<Option<u64>> {
+        // Channels have no topics, so the topic heads the message.
+        let text = match topic {
+            Some(topic) => format!("**{}**\n{}", topic, content),
+            None => content.to_owned(),
+        };
+        let body = match self.kind {
+            WebhookKind::Slack => serde_json::json!({ "text": text }),
+            WebhookKind::Discord => serde_json::json!({ "content": text }),
+        };
+        self.client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("posting to {:?} webhook", self.kind))?;
+        Ok(None)
+    }
+
+    async fn direct_message(&self, _user: &str, _content: &str) -> anyhow::Result<()> {
+        anyhow::bail!("{:?} webhooks cannot send direct messages", self.kind)
+    }
+
+    async fn add_reaction(&self, _message_id: u64, _emoji: &str) -> anyhow::Result<()> {
+        anyhow::bail!("{:?} webhooks cannot add reactions", self.kind)
+    }
+
+    fn permalink(&self, _topic: Option<&str>) -> Option<String> {
+        None
+    }
+}
@@ -1,11 +1,11 @@
 use crate::changelogs::ChangelogFormat;
-use crate::github::GithubClient;
+use crate::github::{GithubClient, Label, RepositoryLabel};
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
-static CONFIG_FILE_NAME: &str = "triagebot.toml";
+pub(crate) static CONFIG_FILE_NAME: &str = "triagebot.toml";
 const REFRESH_EVERY: Duration = Duration::from_secs(2 * 60); // Every two minutes
 
 lazy_static::lazy_static! {
@@ -27,6 +27,35 @@ pub(crate) struct Config {
     pub(crate) autolabel: Option<AutolabelConfig>,
     pub(crate) notify_zulip: Option<NotifyZulipConfig>,
     pub(crate) github_releases: Option<GitHubReleasesConfig>,
+    pub(crate) exclusive_labels: Option<ExclusiveLabelsConfig>,
+    pub(crate) labels: Option<LabelsConfig>,
+    pub(crate) backport: Option<BackportConfig>,
+    pub(crate) fcp: Option<FcpConfig>,
+}
+
+impl Config {
+    /// Checks what parsing the configuration doesn't, like the syntax of templates.
+    fn validate(&self) -> Result<(), String> {
+        if let Some(autolabel) = &self.autolabel {
+            for (label, config) in &autolabel.labels {
+                config.validate(label)?;
+            }
+        }
+        if let Some(notify_zulip) = &self.notify_zulip {
+            for (label, config) in &notify_zulip.labels {
+                config.validate(label)?;
+            }
+        }
+        if let Some(prioritize) = &self.prioritize {
+            prioritize.validate()?;
+        }
+        if let Some(major_change) = &self.major_change {
+            for process in major_change.processes() {
+                process.validate()?;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
@@ -60,11 +89,24 @@ impl PingConfig {
 }
 
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub(crate) struct PingTeamConfig {
     pub(crate) message: String,
     #[serde(default)]
     pub(crate) alias: HashSet<String>,
     pub(crate) label: Option<String>,
+    /// Hours before the team can be pinged again on the same issue; 0 (the default) for no
+    /// cooldown.
+    #[serde(default)]
+    pub(crate) cooldown_hours: u32,
+    /// How many times the team can be pinged in any 24 hours, across all issues.
+    pub(crate) max_per_day: Option<u32>,
+    /// GitHub usernames of the members, for groups which are not in the team repository.
+    pub(crate) members: Option<Vec<String>>,
+    /// Zulip stream notified whenever the group is pinged.
+    pub(crate) zulip_stream: Option<u64>,
+    /// Label added to issues on which the group was pinged.
+    pub(crate) pinged_label: Option<String>,
 }
 
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
@@ -78,11 +120,137 @@ pub(crate) struct AssignConfig {
 pub(crate) struct RelabelConfig {
     #[serde(default)]
     pub(crate) allow_unauthenticated: Vec<String>,
+    /// Label patterns which only the author of the issue may add or remove
+    /// (in addition to the teams granted access by a matching rule).
+    #[serde(default)]
+    pub(crate) author_only: Vec<String>,
+    #[serde(default, rename = "rule")]
+    pub(crate) rules: Vec<RelabelRuleConfig>,
+}
+
+/// Restricts who may add or remove labels matching `labels`.
+///
+/// An empty team list means the rule does not restrict that direction.
+#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+pub(crate) struct RelabelRuleConfig {
+    pub(crate) labels: Vec<String>,
+    /// Teams (from the team repository) which may add the labels.
+    #[serde(default)]
+    pub(crate) add: Vec<String>,
+    /// Teams (from the team repository) which may remove the labels.
+    #[serde(default)]
+    pub(crate) remove: Vec<String>,
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+pub(crate) struct LabelsConfig {
+    // label name -> definition
+    #[serde(flatten)]
+    pub(crate) labels: HashMap<String, LabelConfig>,
+}
+
+impl LabelsConfig {
+    pub(crate) fn definition(&self, name: &str) -> Option<RepositoryLabel> {
+        let config = self.labels.get(name)?;
+        Some(RepositoryLabel {
+            name: name.to_owned(),
+            color: config.color.trim_start_matches('#').to_owned(),
+            description: config.description.clone(),
+        })
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct LabelConfig {
+    /// Hex color code, e.g. `d73a4a`.
+    pub(crate) color: String,
+    pub(crate) description: Option<String>,
+    /// Previous names of this label. Existing labels with these names are
+    /// renamed, or merged into this label if it already exists.
+    #[serde(default)]
+    pub(crate) renamed_from: Vec<String>,
 }
 
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+pub(crate) struct ExclusiveLabelsConfig {
+    // group name -> label patterns
+    // at most one label matching the group's patterns may be applied to an issue
+    #[serde(flatten)]
+    pub(crate) groups: HashMap<String, Vec<String>>,
+}
+
+impl ExclusiveLabelsConfig {
+    /// Returns the names of the groups the label belongs to.
+    pub(crate) fn groups_of(&self, label: &str) -> Vec<&str> {
+        self.groups
+            .iter()
+            .filter(|(_, patterns)| matches_group(patterns, label))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Removes the labels conflicting with the ones newly added to `labels`
+    /// (i.e., not in `previous`). When several labels of the same group are
+    /// added, the last one wins.
+    pub(crate) fn resolve(&self, previous: &[Label], mut labels: Vec<Label>) -> Vec<Label> {
+        for patterns in self.groups.values() {
+            let keep = labels
+                .iter()
+                .rev()
+                .find(|l| !previous.contains(l) && matches_group(patterns, &l.name))
+                .cloned();
+            if let Some(keep) = keep {
+                labels.retain(|l| *l == keep || !matches_group(patterns, &l.name));
+            }
+        }
+        labels
+    }
+}
+
+fn matches_group(patterns: &[String], label: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| match glob::Pattern::new(pattern) {
+            Ok(glob) => glob.matches(label),
+            Err(err) => {
+                log::error!("Invalid glob pattern: {}", err);
+                false
+            }
+        })
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub(crate) struct PrioritizeConfig {
     pub(crate) label: String,
+    /// The prefix of the priority labels, which are mutually exclusive.
+    #[serde(default = "PrioritizeConfig::default_priority_prefix")]
+    pub(crate) priority_prefix: String,
+    /// The Zulip stream in which a topic is opened for each prioritization request; a shorthand
+    /// for a Zulip `channel`.
+    pub(crate) zulip_stream: Option<u64>,
+    /// The channel in which each prioritization request is announced, on any chat backend.
+    pub(crate) channel: Option<ChannelConfig>,
+}
+
+impl PrioritizeConfig {
+    fn default_priority_prefix() -> String {
+        "P-".to_owned()
+    }
+
+    pub(crate) fn channel(&self) -> Option<ChannelConfig> {
+        ChannelConfig::or_zulip_stream(&self.channel, self.zulip_stream)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.channel.is_some() && self.zulip_stream.is_some() {
+            return Err(
+                "[prioritize]: only one of `zulip_stream` and `channel` can be given".to_owned(),
+            );
+        }
+        Ok(())
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
@@ -105,9 +273,62 @@ impl AutolabelConfig {
 
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
 pub(crate) struct AutolabelLabelConfig {
+    #[serde(default)]
     pub(crate) trigger_labels: Vec<String>,
     #[serde(default)]
     pub(crate) exclude_labels: Vec<String>,
+    /// Globs matched against the files changed by pull requests, e.g. `src/librustdoc/**`.
+    #[serde(default)]
+    pub(crate) trigger_files: Vec<String>,
+    /// Regexes matched against the title and description of new issues.
+    #[serde(default)]
+    pub(crate) trigger_body: Vec<String>,
+    /// Section headings looked for in the description of new issues, e.g. `Platform: Windows`.
+    #[serde(default)]
+    pub(crate) trigger_sections: Vec<String>,
+    /// Whether `trigger_body` and `trigger_sections` also match inside code blocks.
+    #[serde(default)]
+    pub(crate) match_code_blocks: bool,
+    /// Author associations of new issues and pull requests, e.g. `FIRST_TIME_CONTRIBUTOR`.
+    #[serde(default)]
+    pub(crate) trigger_author_associations: Vec<String>,
+    /// States of reviews submitted on pull requests, e.g. `changes_requested`.
+    ///
+    /// Labels triggered by reviews or pushes are states of the pull request, like
+    /// `S-waiting-on-author` and `S-waiting-on-review`: adding one removes the others.
+    #[serde(default)]
+    pub(crate) trigger_reviews: Vec<String>,
+    /// Whether the author pushing to their pull request triggers the label.
+    #[serde(default)]
+    pub(crate) trigger_author_push: bool,
+    /// Tera template of a comment posted when the label is added, e.g. to welcome new
+    /// contributors. It can use `author`, `number`, `title`, `repo` and `label`.
+    pub(crate) comment: Option<String>,
+}
+
+impl AutolabelLabelConfig {
+    /// Whether the label is a state of pull requests; see `trigger_reviews`.
+    pub(crate) fn is_state(&self) -> bool {
+        !self.trigger_reviews.is_empty() || self.trigger_author_push
+    }
+
+    fn validate(&self, label: &str) -> Result<(), String> {
+        for re in &self.trigger_body {
+            regex::Regex::new(re)
+                .map_err(|e| format!("[autolabel.\"{}\"]: invalid regex: {}", label, e))?;
+        }
+        if let Some(comment) = &self.comment {
+            let mut sample = tera::Context::new();
+            sample.insert("author", "author");
+            sample.insert("number", &1);
+            sample.insert("title", "Title");
+            sample.insert("repo", "rust-lang/rust");
+            sample.insert("label", label);
+            check_template(comment, &sample)
+                .map_err(|e| format!("[autolabel.\"{}\"]: {}", label, e))?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
@@ -116,22 +337,260 @@ pub(crate) struct NotifyZulipConfig {
     pub(crate) labels: HashMap<String, NotifyZulipLabelConfig>,
 }
 
+/// The topic and messages are Tera templates; see `handlers::notify_zulip` for their variables.
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
 pub(crate) struct NotifyZulipLabelConfig {
-    pub(crate) zulip_stream: u64,
+    /// The Zulip stream to post to; a shorthand for a Zulip `channel`.
+    pub(crate) zulip_stream: Option<u64>,
+    /// The channel to post to, on any chat backend.
+    pub(crate) channel: Option<ChannelConfig>,
     pub(crate) topic: String,
     pub(crate) message_on_add: Option<String>,
     pub(crate) message_on_remove: Option<String>,
+    /// Posted when an issue with a label matching the key is opened.
+    pub(crate) message_on_open: Option<String>,
+    pub(crate) message_on_close: Option<String>,
+    pub(crate) message_on_reopen: Option<String>,
+    /// Posted when a pull request is merged, instead of `message_on_close`.
+    pub(crate) message_on_merge: Option<String>,
+    /// Posted when a review is submitted on a pull request.
+    pub(crate) message_on_review: Option<String>,
     #[serde(default)]
     pub(crate) required_labels: Vec<String>,
 }
 
+impl NotifyZulipLabelConfig {
+    /// Returns the Tera source of `template`, converting the `{number}` and `{title}`
+    /// placeholders of older configurations.
+    pub(crate) fn tera_source(template: &str) -> String {
+        lazy_static::lazy_static! {
+            static ref PLACEHOLDER: regex::Regex =
+                regex::Regex::new(r"(^|[^{])\{(number|title)\}").unwrap();
+        }
+        // Twice, as adjacent placeholders don't match at once.
+        let template = PLACEHOLDER.replace_all(template, "$1{{ $2 }}");
+        PLACEHOLDER
+            .replace_all(&template, "$1{{ $2 }}")
+            .into_owned()
+    }
+
+    pub(crate) fn channel(&self) -> ChannelConfig {
+        ChannelConfig::or_zulip_stream(&self.channel, self.zulip_stream)
+            .expect("validated configuration")
+    }
+
+    fn validate(&self, label: &str) -> Result<(), String> {
+        if self.channel.is_some() == self.zulip_stream.is_some() {
+            return Err(format!(
+                "[notify-zulip.\"{}\"]: exactly one of `zulip_stream` and `channel` is required",
+                label
+            ));
+        }
+        let templates = std::iter::once(&self.topic)
+            .chain(&self.message_on_add)
+            .chain(&self.message_on_remove)
+            .chain(&self.message_on_open)
+            .chain(&self.message_on_close)
+            .chain(&self.message_on_reopen)
+            .chain(&self.message_on_merge)
+            .chain(&self.message_on_review);
+        let sample = Self::sample_context();
+        for template in templates {
+            check_template(&Self::tera_source(template), &sample)
+                .map_err(|e| format!("[notify-zulip.\"{}\"]: {}", label, e))?;
+        }
+        Ok(())
+    }
+
+    /// A context with every variable of the templates, as set by
+    /// `handlers::notify_zulip::template_context`.
+    fn sample_context() -> tera::Context {
+        let mut context = tera::Context::new();
+        context.insert("number", &1);
+        context.insert("title", "Title");
+        context.insert("url", "https://github.com/rust-lang/rust/issues/1");
+        context.insert("author", "author");
+        context.insert("labels", &["I-nominated"]);
+        context.insert("assignees", &["assignee"]);
+        context.insert("repo", "rust-lang/rust");
+        context.insert("label", "I-nominated");
+        context.insert("actor", "actor");
+        context.insert("review_state", "approved");
+        context.insert("excerpt", "Excerpt");
+        context
+    }
+}
+
+/// Checks that `template` is a valid Tera template which only uses the variables of `sample`, by
+/// rendering it.
+fn check_template(template: &str, sample: &tera::Context) -> Result<(), String> {
+    match tera::Tera::one_off(template, sample, false) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            // The details are in the sources of the error.
+            let mut message = format!("invalid template: {}", e);
+            let mut source = std::error::Error::source(&e);
+            while let Some(error) = source {
+                message.push_str(&format!(": {}", error));
+                source = std::error::Error::source(error);
+            }
+            Err(message)
+        }
+    }
+}
+
+/// A channel of a chat service; see `chat` for the backends.
+#[derive(PartialEq, Eq, Debug, Clone, serde::Deserialize)]
+#[serde(tag = "backend", rename_all = "kebab-case")]
+pub(crate) enum ChannelConfig {
+    Zulip {
+        stream: u64,
+    },
+    /// A Slack incoming webhook, named after its environment variable.
+    Slack {
+        webhook: String,
+    },
+    /// A Discord webhook, named after its environment variable.
+    Discord {
+        webhook: String,
+    },
+}
+
+impl ChannelConfig {
+    /// Returns `channel`, or else the Zulip channel of the `zulip_stream` shorthand.
+    fn or_zulip_stream(channel: &Option<ChannelConfig>, stream: Option<u64>) -> Option<Self> {
+        match (channel, stream) {
+            (Some(channel), _) => Some(channel.clone()),
+            (None, stream) => stream.map(|stream| ChannelConfig::Zulip { stream }),
+        }
+    }
+
+    /// The stream of a Zulip channel, in which issues can have their own topics.
+    pub(crate) fn zulip_stream(&self) -> Option<u64> {
+        match self {
+            ChannelConfig::Zulip { stream } => Some(*stream),
+            _ => None,
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
 pub(crate) struct MajorChangeConfig {
+    /// The main proposal process, configured in `[major-change]` itself.
+    #[serde(flatten)]
+    pub(crate) main: MajorChangeProcess,
+    /// Further independent proposal processes, e.g. `[major-change.processes.libs]`.
+    #[serde(default)]
+    pub(crate) processes: HashMap<String, MajorChangeProcess>,
+}
+
+impl MajorChangeConfig {
+    pub(crate) fn processes(&self) -> impl Iterator<Item = &MajorChangeProcess> {
+        std::iter::once(&self.main).chain(self.processes.values())
+    }
+
+    /// Returns the process which `label` triggers.
+    pub(crate) fn process_triggered_by(&self, label: &str) -> Option<&MajorChangeProcess> {
+        self.processes().find(|p| p.label == label)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+pub(crate) struct MajorChangeProcess {
+    /// The label which makes an issue a proposal of this process.
+    #[serde(default = "MajorChangeProcess::default_label")]
+    pub(crate) label: String,
+    /// The label of accepted proposals.
+    #[serde(default = "MajorChangeProcess::default_accepted_label")]
+    pub(crate) accepted_label: String,
     pub(crate) zulip_ping: String,
     pub(crate) second_label: String,
     pub(crate) meeting_label: String,
-    pub(crate) zulip_stream: u64,
+    /// The Zulip stream of the proposal topics; a shorthand for a Zulip `channel`.
+    pub(crate) zulip_stream: Option<u64>,
+    /// The channel the proposals are announced in, on any chat backend.
+    pub(crate) channel: Option<ChannelConfig>,
+    /// How long a seconded proposal waits for concerns before it is accepted.
+    #[serde(default = "MajorChangeProcess::default_waiting_period_days")]
+    pub(crate) waiting_period_days: u32,
+    #[serde(default)]
+    pub(crate) templates: MajorChangeTemplates,
+}
+
+impl MajorChangeProcess {
+    fn default_label() -> String {
+        "major-change".to_owned()
+    }
+
+    fn default_accepted_label() -> String {
+        "major-change-accepted".to_owned()
+    }
+
+    fn default_waiting_period_days() -> u32 {
+        10
+    }
+
+    pub(crate) fn channel(&self) -> ChannelConfig {
+        ChannelConfig::or_zulip_stream(&self.channel, self.zulip_stream)
+            .expect("validated configuration")
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.channel.is_some() == self.zulip_stream.is_some() {
+            return Err(format!(
+                "[major-change] process `{}`: exactly one of `zulip_stream` and `channel` is \
+                 required",
+                self.label
+            ));
+        }
+        // The variables of every message, see `handlers::major_change::template_context`.
+        let mut sample = tera::Context::new();
+        sample.insert("number", &1);
+        sample.insert("title", "Title");
+        sample.insert("url", "https://github.com/rust-lang/compiler-team/issues/1");
+        sample.insert("zulip_ping", &self.zulip_ping);
+        sample.insert("waiting_period_days", &self.waiting_period_days);
+        // The variables of the messages about a comment.
+        const COMMENT: &[&str] = &["comment_url", "user"];
+        const CONCERN: &[&str] = &["comment_url", "user", "concern"];
+        let t = &self.templates;
+        let templates: [(&Option<String>, &[&str]); 8] = [
+            (&t.new_proposal_comment, &["topic_url"]),
+            (&t.new_proposal_zulip, &[]),
+            (&t.seconded_zulip, COMMENT),
+            (&t.accepted_zulip, &[]),
+            (&t.auto_accepted_comment, &[]),
+            (&t.concern_raised_zulip, CONCERN),
+            (&t.concern_resolved_zulip, CONCERN),
+            (&t.withdrawn_zulip, COMMENT),
+        ];
+        for (template, variables) in templates.iter() {
+            if let Some(template) = template {
+                let mut sample = sample.clone();
+                for variable in variables.iter() {
+                    sample.insert(*variable, "sample");
+                }
+                check_template(template, &sample)
+                    .map_err(|e| format!("[major-change] process `{}`: {}", self.label, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tera templates replacing the default messages of a major change process.
+#[derive(PartialEq, Eq, Debug, Default, serde::Deserialize)]
+pub(crate) struct MajorChangeTemplates {
+    /// Comment on new proposals, pointing to the Zulip topic.
+    pub(crate) new_proposal_comment: Option<String>,
+    pub(crate) new_proposal_zulip: Option<String>,
+    pub(crate) seconded_zulip: Option<String>,
+    pub(crate) accepted_zulip: Option<String>,
+    /// Comment on proposals accepted at the end of their waiting period.
+    pub(crate) auto_accepted_comment: Option<String>,
+    pub(crate) concern_raised_zulip: Option<String>,
+    pub(crate) concern_resolved_zulip: Option<String>,
+    pub(crate) withdrawn_zulip: Option<String>,
 }
 
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
@@ -142,16 +601,25 @@ pub(crate) async fn get(gh: &GithubClient, repo: &str) -> Result<Arc<Config>, Co
         log::trace!("returning config for {} from cache", repo);
         config
     } else {
-        log::trace!("fetching fresh config for {}", repo);
-        let res = get_fresh_config(gh, repo).await;
-        CONFIG_CACHE
-            .write()
-            .unwrap()
-            .insert(repo.to_string(), (res.clone(), Instant::now()));
-        res
+        refresh(gh, repo, "master").await
     }
 }
 
+/// Fetches the configuration from `branch` bypassing the cache, e.g. after a push changed it.
+pub(crate) async fn refresh(
+    gh: &GithubClient,
+    repo: &str,
+    branch: &str,
+) -> Result<Arc<Config>, ConfigurationError> {
+    log::trace!("fetching fresh config for {} from {}", repo, branch);
+    let res = get_fresh_config(gh, repo, branch).await;
+    CONFIG_CACHE
+        .write()
+        .unwrap()
+        .insert(repo.to_string(), (res.clone(), Instant::now()));
+    res
+}
+
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub(crate) struct GitHubReleasesConfig {
@@ -161,6 +629,45 @@ pub(crate) struct GitHubReleasesConfig {
     pub(crate) changelog_branch: String,
 }
 
+/// Opens backport PRs for accepted beta backports.
+#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct BackportConfig {
+    /// The branch accepted beta backports are cherry-picked onto.
+    #[serde(default = "BackportConfig::default_beta_branch")]
+    pub(crate) beta_branch: String,
+}
+
+impl BackportConfig {
+    fn default_beta_branch() -> String {
+        "beta".to_owned()
+    }
+}
+
+/// Runs final comment periods, with the members of the teams of an issue as reviewers.
+#[derive(PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct FcpConfig {
+    /// Team label (e.g. `T-lang`) -> team name in the team repository.
+    pub(crate) teams: HashMap<String, String>,
+    /// How many days the final comment period lasts once the proposal is reviewed.
+    #[serde(default = "FcpConfig::default_duration_days")]
+    pub(crate) duration_days: u32,
+    /// How many reviews may be outstanding for the final comment period to start.
+    #[serde(default = "FcpConfig::default_max_unreviewed")]
+    pub(crate) max_unreviewed: usize,
+}
+
+impl FcpConfig {
+    fn default_duration_days() -> u32 {
+        10
+    }
+
+    fn default_max_unreviewed() -> usize {
+        2
+    }
+}
+
 fn get_cached_config(repo: &str) -> Option<Result<Arc<Config>, ConfigurationError>> {
     let cache = CONFIG_CACHE.read().unwrap();
     cache.get(repo).and_then(|(config, fetch_time)| {
@@ -175,13 +682,16 @@ fn get_cached_config(repo: &str) -> Option<Result<Arc<Config>, ConfigurationErro
 async fn get_fresh_config(
     gh: &GithubClient,
     repo: &str,
+    branch: &str,
 ) -> Result<Arc<Config>, ConfigurationError> {
     let contents = gh
-        .raw_file(repo, "master", CONFIG_FILE_NAME)
+        .raw_file(repo, branch, CONFIG_FILE_NAME)
         .await
         .map_err(|e| ConfigurationError::Http(Arc::new(e)))?
         .ok_or(ConfigurationError::Missing)?;
-    let config = Arc::new(toml::from_slice::<Config>(&contents).map_err(ConfigurationError::Toml)?);
+    let config = toml::from_slice::<Config>(&contents).map_err(ConfigurationError::Toml)?;
+    config.validate().map_err(ConfigurationError::Invalid)?;
+    let config = Arc::new(config);
     log::debug!("fresh configuration for {}: {:?}", repo, config);
     Ok(config)
 }
@@ -190,6 +700,8 @@ async fn get_fresh_config(
 pub enum ConfigurationError {
     Missing,
     Toml(toml::de::Error),
+    /// The configuration parsed, but is not valid, e.g. because of a malformed template.
+    Invalid(String),
     Http(Arc<anyhow::Error>),
 }
 
@@ -206,6 +718,9 @@ impl fmt::Display for ConfigurationError {
             ConfigurationError::Toml(e) => {
                 write!(f, "Malformed `triagebot.toml` in master branch.\n{}", e)
             }
+            ConfigurationError::Invalid(e) => {
+                write!(f, "Invalid `triagebot.toml` in master branch.\n{}", e)
+            }
             ConfigurationError::Http(_) => {
                 write!(f, "Failed to query configuration for this repository.")
             }
@@ -224,6 +739,11 @@ mod tests {
             allow-unauthenticated = [
                 "C-*"
             ]
+            author-only = ["S-waiting-on-review"]
+
+            [[relabel.rule]]
+            labels = ["beta-accepted"]
+            add = ["release"]
 
             [assign]
 
@@ -237,6 +757,11 @@ mod tests {
             message = """\
             Testing\
             """
+            cooldown-hours = 1
+            max-per-day = 5
+            members = ["alice", "bob"]
+            zulip-stream = 1234
+            pinged-label = "pinged-wg-meta"
 
             [nominate.teams]
             compiler = "T-compiler"
@@ -252,6 +777,11 @@ mod tests {
                 message: "So many people!".to_owned(),
                 label: Some("T-compiler".to_owned()),
                 alias: HashSet::new(),
+                cooldown_hours: 0,
+                max_per_day: None,
+                members: None,
+                zulip_stream: None,
+                pinged_label: None,
             },
         );
         ping_teams.insert(
@@ -260,6 +790,11 @@ mod tests {
                 message: "Testing".to_owned(),
                 label: None,
                 alias: HashSet::new(),
+                cooldown_hours: 1,
+                max_per_day: Some(5),
+                members: Some(vec!["alice".to_owned(), "bob".to_owned()]),
+                zulip_stream: Some(1234),
+                pinged_label: Some("pinged-wg-meta".to_owned()),
             },
         );
         let mut nominate_teams = HashMap::new();
@@ -272,6 +807,12 @@ mod tests {
             Config {
                 relabel: Some(RelabelConfig {
                     allow_unauthenticated: vec!["C-*".into()],
+                    author_only: vec!["S-waiting-on-review".into()],
+                    rules: vec![RelabelRuleConfig {
+                        labels: vec!["beta-accepted".into()],
+                        add: vec!["release".into()],
+                        remove: vec![],
+                    }],
                 }),
                 assign: Some(AssignConfig { _empty: () }),
                 ping: Some(PingConfig { teams: ping_teams }),
@@ -284,7 +825,235 @@ mod tests {
                 autolabel: None,
                 notify_zulip: None,
                 github_releases: None,
+                exclusive_labels: None,
+                labels: None,
+                backport: None,
+                fcp: None,
             }
         );
     }
+
+    #[test]
+    fn labels() {
+        let config = r##"
+            [T-compiler]
+            color = "#bfd4f2"
+            description = "Relevant to the compiler team"
+            renamed-from = ["T-compiler-old"]
+
+            [bug]
+            color = "d73a4a"
+        "##;
+        let config = toml::from_str::<LabelsConfig>(&config).unwrap();
+        assert_eq!(
+            config.labels["T-compiler"].renamed_from,
+            vec!["T-compiler-old".to_owned()]
+        );
+        assert_eq!(
+            config.definition("T-compiler"),
+            Some(RepositoryLabel {
+                name: "T-compiler".to_owned(),
+                color: "bfd4f2".to_owned(),
+                description: Some("Relevant to the compiler team".to_owned()),
+            })
+        );
+        assert_eq!(
+            config.definition("bug"),
+            Some(RepositoryLabel {
+                name: "bug".to_owned(),
+                color: "d73a4a".to_owned(),
+                description: None,
+            })
+        );
+        assert_eq!(config.definition("T-lang"), None);
+    }
+
+    #[test]
+    fn exclusive_labels() {
+        let config = r#"
+            priority = ["P-*"]
+            waiting-on = ["S-waiting-on-author", "S-waiting-on-review"]
+        "#;
+        let config = toml::from_str::<ExclusiveLabelsConfig>(&config).unwrap();
+        let labels = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| Label {
+                    name: name.to_string(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(config.groups_of("P-high"), vec!["priority"]);
+        assert!(config.groups_of("T-lang").is_empty());
+
+        let previous = labels(&["P-low", "S-waiting-on-author", "T-lang"]);
+        assert_eq!(
+            config.resolve(
+                &previous,
+                labels(&["P-low", "S-waiting-on-author", "T-lang", "P-high"])
+            ),
+            labels(&["S-waiting-on-author", "T-lang", "P-high"])
+        );
+        assert_eq!(
+            config.resolve(
+                &previous,
+                labels(&[
+                    "P-low",
+                    "S-waiting-on-author",
+                    "S-waiting-on-review",
+                    "P-medium",
+                    "P-high"
+                ])
+            ),
+            labels(&["S-waiting-on-review", "P-high"])
+        );
+        // Conflicts which were already present are left alone.
+        let previous = labels(&["P-low", "P-high"]);
+        assert_eq!(
+            config.resolve(&previous, labels(&["P-low", "P-high", "T-lang"])),
+            labels(&["P-low", "P-high", "T-lang"])
+        );
+    }
+
+    #[test]
+    fn autolabel_validation() {
+        let config = r#"
+            [autolabel."I-ICE"]
+            trigger_body = ["error: internal compiler error"]
+        "#;
+        let config = toml::from_str::<Config>(&config).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+
+        let config = r#"
+            [autolabel."I-ICE"]
+            trigger_body = ["error: (internal"]
+        "#;
+        let config = toml::from_str::<Config>(&config).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn notify_zulip_templates() {
+        assert_eq!(
+            NotifyZulipLabelConfig::tera_source("{number}{title} {{ url }} {{number}}"),
+            "{{ number }}{{ title }} {{ url }} {{number}}"
+        );
+
+        let config = r##"
+            [notify-zulip."I-nominated"]
+            zulip_stream = 1
+            topic = "#{number} {title}"
+            message_on_add = "{% if actor %}Nominated by {{ actor }}{% endif %}"
+        "##;
+        let config = toml::from_str::<Config>(&config).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+
+        let config = r#"
+            [notify-zulip."I-nominated"]
+            zulip_stream = 1
+            topic = "{{ title"
+        "#;
+        let config = toml::from_str::<Config>(&config).unwrap();
+        assert!(config.validate().is_err());
+
+        let config = r#"
+            [notify-zulip."I-nominated"]
+            zulip_stream = 1
+            topic = "{{ titel }}"
+        "#;
+        let config = toml::from_str::<Config>(&config).unwrap();
+        assert!(config.validate().is_err());
+
+        let config = r#"
+            [notify-zulip."I-nominated"]
+            channel = { backend = "slack", webhook = "compiler" }
+            topic = "{{ title }}"
+        "#;
+        let config = toml::from_str::<Config>(&config).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(
+            config.notify_zulip.unwrap().labels["I-nominated"].channel(),
+            ChannelConfig::Slack {
+                webhook: "compiler".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn major_change_validation() {
+        let config = r#"
+            [major-change]
+            zulip_ping = "T-compiler"
+            second_label = "final-comment-period"
+            meeting_label = "to-announce"
+            zulip_stream = 233931
+
+            [major-change.processes.libs]
+            label = "libs-proposal"
+            accepted_label = "libs-proposal-accepted"
+            zulip_ping = "T-libs"
+            second_label = "libs-seconded"
+            meeting_label = "libs-to-announce"
+            channel = { backend = "discord", webhook = "libs" }
+        "#;
+        let config = toml::from_str::<Config>(&config).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+        let major_change = config.major_change.unwrap();
+        assert_eq!(
+            major_change.main.channel(),
+            ChannelConfig::Zulip { stream: 233931 }
+        );
+        assert_eq!(
+            major_change.processes["libs"].channel(),
+            ChannelConfig::Discord {
+                webhook: "libs".to_owned()
+            }
+        );
+
+        let config = r#"
+            [major-change]
+            zulip_ping = "T-compiler"
+            second_label = "final-comment-period"
+            meeting_label = "to-announce"
+        "#;
+        let config = toml::from_str::<Config>(&config).unwrap();
+        assert!(config.validate().is_err());
+
+        let config = r#"
+            [major-change]
+            zulip_ping = "T-compiler"
+            second_label = "final-comment-period"
+            meeting_label = "to-announce"
+            zulip_stream = 233931
+
+            [major-change.templates]
+            seconded_zulip = "Seconded by @{{ user }}."
+        "#;
+        let config = toml::from_str::<Config>(&config).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+
+        // `concern` is only available to the concern messages.
+        let config = r#"
+            [major-change]
+            zulip_ping = "T-compiler"
+            second_label = "final-comment-period"
+            meeting_label = "to-announce"
+            zulip_stream = 233931
+
+            [major-change.templates]
+            seconded_zulip = "Seconded despite {{ concern }}."
+        "#;
+        let config = toml::from_str::<Config>(&config).unwrap();
+        assert!(config.validate().is_err());
+
+        let config = r#"
+            [prioritize]
+            label = "I-prioritize"
+            zulip_stream = 245100
+            channel = { backend = "slack", webhook = "compiler" }
+        "#;
+        let config = toml::from_str::<Config>(&config).unwrap();
+        assert!(config.validate().is_err());
+    }
 }
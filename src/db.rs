@@ -3,8 +3,17 @@ use native_tls::{Certificate, TlsConnector};
 use postgres_native_tls::MakeTlsConnector;
 pub use tokio_postgres::Client as DbClient;
 
+pub mod backports;
+pub mod fcps;
+pub mod glacier;
+pub mod label_changes;
+pub mod major_changes;
+pub mod nominations;
 pub mod notifications;
+pub mod pings;
+pub mod prioritizations;
 pub mod rustc_commits;
+pub mod zulip_topics;
 
 const CERT_URL: &str = "https://s3.amazonaws.com/rds-downloads/rds-ca-2019-root.pem";
 
@@ -138,4 +147,163 @@ CREATE TABLE rustc_commits (
 );
 ",
     "ALTER TABLE rustc_commits ADD COLUMN pr INTEGER;",
+    "
+CREATE TABLE label_changes (
+    change_id BIGSERIAL PRIMARY KEY,
+    repo TEXT NOT NULL,
+    issue_number BIGINT NOT NULL,
+    actor TEXT,
+    comment_url TEXT,
+    handler TEXT,
+    labels_before TEXT[] NOT NULL,
+    labels_after TEXT[] NOT NULL,
+    time TIMESTAMP WITH TIME ZONE NOT NULL
+);
+",
+    "CREATE INDEX label_changes_issue_idx ON label_changes (repo, issue_number);",
+    "
+CREATE TABLE team_pings (
+    repo TEXT NOT NULL,
+    issue_number BIGINT NOT NULL,
+    team TEXT NOT NULL,
+    time TIMESTAMP WITH TIME ZONE NOT NULL
+);
+",
+    "CREATE INDEX team_pings_team_time_idx ON team_pings (team, time);",
+    "
+CREATE TABLE ping_opt_outs (
+    user_id BIGINT NOT NULL,
+    team TEXT NOT NULL,
+    PRIMARY KEY (user_id, team)
+);
+",
+    "
+CREATE TABLE ping_subscriptions (
+    user_id BIGINT NOT NULL,
+    username TEXT NOT NULL,
+    team TEXT NOT NULL,
+    PRIMARY KEY (user_id, team)
+);
+",
+    "
+CREATE TABLE nominations (
+    nomination_id BIGSERIAL PRIMARY KEY,
+    repo TEXT NOT NULL,
+    issue_number BIGINT NOT NULL,
+    team TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    nominator TEXT NOT NULL,
+    reason TEXT,
+    time TIMESTAMP WITH TIME ZONE NOT NULL,
+    resolved_by TEXT,
+    resolution TEXT,
+    resolved_at TIMESTAMP WITH TIME ZONE
+);
+",
+    "CREATE INDEX nominations_repo_issue_idx ON nominations (repo, issue_number);",
+    "
+CREATE TABLE backports (
+    repo TEXT NOT NULL,
+    pr_number BIGINT NOT NULL,
+    channel TEXT NOT NULL,
+    state TEXT NOT NULL,
+    backport_pr BIGINT,
+    error TEXT,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (repo, pr_number, channel)
+);
+",
+    "
+CREATE TABLE prioritizations (
+    repo TEXT NOT NULL,
+    issue_number BIGINT NOT NULL,
+    requested_by TEXT NOT NULL,
+    requested_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    priority TEXT,
+    prioritized_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (repo, issue_number)
+);
+",
+    "
+CREATE TABLE major_changes (
+    repo TEXT NOT NULL,
+    issue_number BIGINT NOT NULL,
+    state TEXT NOT NULL,
+    proposed_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    seconded_at TIMESTAMP WITH TIME ZONE,
+    accepted_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (repo, issue_number)
+);
+",
+    "
+CREATE TABLE major_change_concerns (
+    concern_id BIGSERIAL PRIMARY KEY,
+    repo TEXT NOT NULL,
+    issue_number BIGINT NOT NULL,
+    title TEXT NOT NULL,
+    raised_by TEXT NOT NULL,
+    raised_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    resolved_by TEXT,
+    resolved_at TIMESTAMP WITH TIME ZONE
+);
+",
+    "CREATE INDEX major_change_concerns_repo_issue_idx ON major_change_concerns (repo, issue_number);",
+    "
+CREATE TABLE fcps (
+    fcp_id BIGSERIAL PRIMARY KEY,
+    repo TEXT NOT NULL,
+    issue_number BIGINT NOT NULL,
+    disposition TEXT NOT NULL,
+    state TEXT NOT NULL,
+    proposer TEXT NOT NULL,
+    comment_id BIGINT NOT NULL,
+    proposed_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    started_at TIMESTAMP WITH TIME ZONE,
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+",
+    "CREATE INDEX fcps_repo_issue_idx ON fcps (repo, issue_number);",
+    "
+CREATE TABLE fcp_reviews (
+    fcp_id BIGINT NOT NULL REFERENCES fcps (fcp_id),
+    reviewer TEXT NOT NULL,
+    reviewed_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (fcp_id, reviewer)
+);
+",
+    "
+CREATE TABLE fcp_concerns (
+    concern_id BIGSERIAL PRIMARY KEY,
+    fcp_id BIGINT NOT NULL REFERENCES fcps (fcp_id),
+    title TEXT NOT NULL,
+    raised_by TEXT NOT NULL,
+    raised_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    resolved_by TEXT,
+    resolved_at TIMESTAMP WITH TIME ZONE
+);
+",
+    "
+CREATE TABLE glacier_ices (
+    issue_number BIGINT NOT NULL,
+    file TEXT NOT NULL,
+    added_at TIMESTAMP WITH TIME ZONE,
+    fixed_at TIMESTAMP WITH TIME ZONE,
+    fixed_in TEXT,
+    PRIMARY KEY (issue_number, file)
+);
+",
+    "
+CREATE TABLE zulip_topics (
+    stream_id BIGINT NOT NULL,
+    topic TEXT NOT NULL,
+    repo TEXT NOT NULL,
+    issue_number BIGINT NOT NULL,
+    linked_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (stream_id, topic)
+);
+",
+    "CREATE INDEX zulip_topics_repo_issue_idx ON zulip_topics (repo, issue_number);",
+    "ALTER TABLE notifications ADD COLUMN snoozed_until TIMESTAMP WITH TIME ZONE;",
+    "ALTER TABLE notifications ADD COLUMN remind_at TIMESTAMP WITH TIME ZONE;",
+    "ALTER TABLE glacier_ices ADD COLUMN repo TEXT;",
 ];
@@ -0,0 +1,126 @@
+use anyhow::Context as _;
+use chrono::{DateTime, FixedOffset};
+use tokio_postgres::Client as DbClient;
+
+/// The PR was nominated for backporting.
+pub const NOMINATED: &str = "nominated";
+/// The backport was accepted, but no backport PR exists yet.
+pub const ACCEPTED: &str = "accepted";
+/// A backport PR was opened.
+pub const OPENED: &str = "opened";
+/// The backport PR was merged.
+pub const MERGED: &str = "merged";
+/// The backport PR could not be opened automatically.
+pub const FAILED: &str = "failed";
+/// The nomination was resolved without accepting the backport.
+pub const DECLINED: &str = "declined";
+
+/// The backport of a PR to a release channel.
+#[derive(Debug, serde::Serialize)]
+pub struct Backport {
+    pub repo: String,
+    pub pr_number: u64,
+    /// `beta` or `stable`.
+    pub channel: String,
+    /// One of `NOMINATED`, `ACCEPTED`, `OPENED`, `MERGED`, `FAILED` or `DECLINED`.
+    pub state: String,
+    pub backport_pr: Option<u64>,
+    /// Why the backport PR could not be opened.
+    pub error: Option<String>,
+    pub updated_at: DateTime<FixedOffset>,
+}
+
+/// Creates or updates the backport of `backport.pr_number` to `backport.channel`.
+pub async fn upsert_backport(db: &DbClient, backport: &Backport) -> anyhow::Result<()> {
+    db.execute(
+        "INSERT INTO backports (repo, pr_number, channel, state, backport_pr, error, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (repo, pr_number, channel) DO UPDATE SET
+            state = EXCLUDED.state,
+            backport_pr = EXCLUDED.backport_pr,
+            error = EXCLUDED.error,
+            updated_at = EXCLUDED.updated_at",
+        &[
+            &backport.repo,
+            &(backport.pr_number as i64),
+            &backport.channel,
+            &backport.state,
+            &backport.backport_pr.map(|pr| pr as i64),
+            &backport.error,
+            &backport.updated_at,
+        ],
+    )
+    .await
+    .context("upserting backport")?;
+    Ok(())
+}
+
+const COLUMNS: &str = "repo, pr_number, channel, state, backport_pr, error, updated_at";
+
+fn from_row(row: tokio_postgres::Row) -> Backport {
+    let pr_number: i64 = row.get(1);
+    let backport_pr: Option<i64> = row.get(4);
+    Backport {
+        repo: row.get(0),
+        pr_number: pr_number as u64,
+        channel: row.get(2),
+        state: row.get(3),
+        backport_pr: backport_pr.map(|pr| pr as u64),
+        error: row.get(5),
+        updated_at: row.get(6),
+    }
+}
+
+pub async fn get_backport(
+    db: &DbClient,
+    repo: &str,
+    pr_number: u64,
+    channel: &str,
+) -> anyhow::Result<Option<Backport>> {
+    let row = db
+        .query_opt(
+            &*format!(
+                "SELECT {} FROM backports WHERE repo = $1 AND pr_number = $2 AND channel = $3",
+                COLUMNS
+            ),
+            &[&repo, &(pr_number as i64), &channel],
+        )
+        .await
+        .context("getting backport")?;
+    Ok(row.map(from_row))
+}
+
+/// Finds the backport which `backport_pr` was opened for.
+pub async fn get_backport_by_pr(
+    db: &DbClient,
+    repo: &str,
+    backport_pr: u64,
+) -> anyhow::Result<Option<Backport>> {
+    let row = db
+        .query_opt(
+            &*format!(
+                "SELECT {} FROM backports WHERE repo = $1 AND backport_pr = $2",
+                COLUMNS
+            ),
+            &[&repo, &(backport_pr as i64)],
+        )
+        .await
+        .context("getting backport by PR")?;
+    Ok(row.map(from_row))
+}
+
+/// Returns the backports of `repo` which are neither merged nor declined, oldest first.
+pub async fn pending_backports(db: &DbClient, repo: &str) -> anyhow::Result<Vec<Backport>> {
+    let rows = db
+        .query(
+            &*format!(
+                "SELECT {} FROM backports WHERE repo = $1 AND state <> $2 AND state <> $3
+                ORDER BY channel, updated_at",
+                COLUMNS
+            ),
+            &[&repo, &MERGED, &DECLINED],
+        )
+        .await
+        .context("getting pending backports")?;
+    Ok(rows.into_iter().map(from_row).collect())
+}
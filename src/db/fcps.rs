@@ -0,0 +1,245 @@
+use anyhow::Context as _;
+use chrono::{DateTime, FixedOffset, Utc};
+use tokio_postgres::Client as DbClient;
+
+/// The FCP was proposed, and waits for reviews.
+pub const PROPOSED: &str = "proposed";
+/// The final comment period is running.
+pub const STARTED: &str = "started";
+/// The final comment period is over.
+pub const FINISHED: &str = "finished";
+/// The FCP was cancelled.
+pub const CANCELLED: &str = "cancelled";
+
+/// A final comment period.
+#[derive(Debug)]
+pub struct Fcp {
+    pub fcp_id: i64,
+    pub repo: String,
+    pub issue_number: u64,
+    /// `merge`, `close` or `postpone`.
+    pub disposition: String,
+    /// One of `PROPOSED`, `STARTED`, `FINISHED` or `CANCELLED`.
+    pub state: String,
+    pub proposer: String,
+    /// The comment listing the reviewers.
+    pub comment_id: usize,
+    pub proposed_at: DateTime<FixedOffset>,
+    pub started_at: Option<DateTime<FixedOffset>>,
+}
+
+/// A reviewer of an FCP.
+#[derive(Debug)]
+pub struct Review {
+    pub reviewer: String,
+    pub reviewed: bool,
+}
+
+/// A concern raised on an FCP.
+#[derive(Debug)]
+pub struct Concern {
+    pub title: String,
+    pub raised_by: String,
+    pub resolved_by: Option<String>,
+}
+
+fn now() -> DateTime<FixedOffset> {
+    Utc::now().into()
+}
+
+fn fcp_from_row(row: tokio_postgres::Row) -> Fcp {
+    Fcp {
+        fcp_id: row.get(0),
+        repo: row.get(1),
+        issue_number: row.get::<_, i64>(2) as u64,
+        disposition: row.get(3),
+        state: row.get(4),
+        proposer: row.get(5),
+        comment_id: row.get::<_, i64>(6) as usize,
+        proposed_at: row.get(7),
+        started_at: row.get(8),
+    }
+}
+
+const FCP_COLUMNS: &str =
+    "fcp_id, repo, issue_number, disposition, state, proposer, comment_id, proposed_at, started_at";
+
+/// Records a proposed FCP along with its reviewers, returning its id.
+pub async fn create_fcp(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+    disposition: &str,
+    proposer: &str,
+    comment_id: usize,
+    reviewers: &[String],
+) -> anyhow::Result<i64> {
+    let row = db
+        .query_one(
+            "INSERT INTO fcps (repo, issue_number, disposition, state, proposer, comment_id, proposed_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING fcp_id",
+            &[
+                &repo,
+                &(issue_number as i64),
+                &disposition,
+                &PROPOSED,
+                &proposer,
+                &(comment_id as i64),
+                &now(),
+            ],
+        )
+        .await
+        .context("inserting fcp")?;
+    let fcp_id: i64 = row.get(0);
+    for reviewer in reviewers {
+        db.execute(
+            "INSERT INTO fcp_reviews (fcp_id, reviewer) VALUES ($1, $2)",
+            &[&fcp_id, reviewer],
+        )
+        .await
+        .context("inserting fcp reviewer")?;
+    }
+    Ok(fcp_id)
+}
+
+/// Returns the proposed or running FCP of an issue, if any.
+pub async fn active_fcp(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+) -> anyhow::Result<Option<Fcp>> {
+    let row = db
+        .query_opt(
+            format!(
+                "SELECT {} FROM fcps
+                WHERE repo = $1 AND issue_number = $2 AND state IN ($3, $4)",
+                FCP_COLUMNS
+            )
+            .as_str(),
+            &[&repo, &(issue_number as i64), &PROPOSED, &STARTED],
+        )
+        .await
+        .context("getting active fcp")?;
+    Ok(row.map(fcp_from_row))
+}
+
+/// Returns the running FCPs of all repositories.
+pub async fn started_fcps(db: &DbClient) -> anyhow::Result<Vec<Fcp>> {
+    let rows = db
+        .query(
+            format!(
+                "SELECT {} FROM fcps WHERE state = $1 ORDER BY started_at",
+                FCP_COLUMNS
+            )
+            .as_str(),
+            &[&STARTED],
+        )
+        .await
+        .context("getting started fcps")?;
+    Ok(rows.into_iter().map(fcp_from_row).collect())
+}
+
+/// Moves an FCP to `state`, recording when it started or ended.
+pub async fn set_state(db: &DbClient, fcp_id: i64, state: &str) -> anyhow::Result<()> {
+    let query = if state == STARTED {
+        "UPDATE fcps SET state = $2, started_at = $3 WHERE fcp_id = $1"
+    } else {
+        "UPDATE fcps SET state = $2, finished_at = $3 WHERE fcp_id = $1"
+    };
+    db.execute(query, &[&fcp_id, &state, &now()])
+        .await
+        .context("updating fcp state")?;
+    Ok(())
+}
+
+/// Marks the review of `reviewer` as done, returning whether it was outstanding.
+pub async fn mark_reviewed(db: &DbClient, fcp_id: i64, reviewer: &str) -> anyhow::Result<bool> {
+    let updated = db
+        .execute(
+            "UPDATE fcp_reviews SET reviewed_at = $3
+            WHERE fcp_id = $1 AND reviewer = $2 AND reviewed_at IS NULL",
+            &[&fcp_id, &reviewer, &now()],
+        )
+        .await
+        .context("marking fcp reviewed")?;
+    Ok(updated > 0)
+}
+
+/// Returns the reviewers of an FCP, sorted by name.
+pub async fn reviews(db: &DbClient, fcp_id: i64) -> anyhow::Result<Vec<Review>> {
+    let rows = db
+        .query(
+            "SELECT reviewer, reviewed_at IS NOT NULL FROM fcp_reviews
+            WHERE fcp_id = $1 ORDER BY lower(reviewer)",
+            &[&fcp_id],
+        )
+        .await
+        .context("getting fcp reviews")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| Review {
+            reviewer: row.get(0),
+            reviewed: row.get(1),
+        })
+        .collect())
+}
+
+/// Raises a concern on an FCP, returning whether it was not already open.
+pub async fn raise_concern(
+    db: &DbClient,
+    fcp_id: i64,
+    title: &str,
+    raised_by: &str,
+) -> anyhow::Result<bool> {
+    let inserted = db
+        .execute(
+            "INSERT INTO fcp_concerns (fcp_id, title, raised_by, raised_at)
+            SELECT $1, $2, $3, $4 WHERE NOT EXISTS (
+                SELECT 1 FROM fcp_concerns
+                WHERE fcp_id = $1 AND title = $2 AND resolved_at IS NULL
+            )",
+            &[&fcp_id, &title, &raised_by, &now()],
+        )
+        .await
+        .context("inserting fcp concern")?;
+    Ok(inserted > 0)
+}
+
+/// Resolves the open concern `title`, returning whether there was one.
+pub async fn resolve_concern(
+    db: &DbClient,
+    fcp_id: i64,
+    title: &str,
+    resolved_by: &str,
+) -> anyhow::Result<bool> {
+    let resolved = db
+        .execute(
+            "UPDATE fcp_concerns SET resolved_by = $3, resolved_at = $4
+            WHERE fcp_id = $1 AND title = $2 AND resolved_at IS NULL",
+            &[&fcp_id, &title, &resolved_by, &now()],
+        )
+        .await
+        .context("resolving fcp concern")?;
+    Ok(resolved > 0)
+}
+
+/// Returns the concerns of an FCP, open or resolved, in the order they were raised.
+pub async fn concerns(db: &DbClient, fcp_id: i64) -> anyhow::Result<Vec<Concern>> {
+    let rows = db
+        .query(
+            "SELECT title, raised_by, resolved_by FROM fcp_concerns
+            WHERE fcp_id = $1 ORDER BY raised_at",
+            &[&fcp_id],
+        )
+        .await
+        .context("getting fcp concerns")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| Concern {
+            title: row.get(0),
+            raised_by: row.get(1),
+            resolved_by: row.get(2),
+        })
+        .collect())
+}
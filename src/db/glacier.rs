@@ -0,0 +1,54 @@
+use anyhow::Context as _;
+use chrono::{DateTime, FixedOffset, Utc};
+use tokio_postgres::Client as DbClient;
+
+/// Records that `file` was added to glacier to track the ICE of `repo`#`issue_number`.
+pub async fn record_ice(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+    file: &str,
+) -> anyhow::Result<()> {
+    db.execute(
+        "INSERT INTO glacier_ices (issue_number, file, added_at, repo) VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING",
+        &[
+            &(issue_number as i64),
+            &file,
+            &DateTime::<FixedOffset>::from(Utc::now()),
+            &repo,
+        ],
+    )
+    .await
+    .context("inserting glacier ice")?;
+    Ok(())
+}
+
+/// Records that `file` was moved to the fixed ICEs by the commits in `fixed_in`.
+///
+/// Returns `None` if the ICE was already known to be fixed, and otherwise the repository of its
+/// issue, if it was recorded when the ICE was added.
+pub async fn record_fixed(
+    db: &DbClient,
+    issue_number: u64,
+    file: &str,
+    fixed_in: &str,
+) -> anyhow::Result<Option<Option<String>>> {
+    let row = db
+        .query_opt(
+            "INSERT INTO glacier_ices (issue_number, file, fixed_at, fixed_in) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (issue_number, file) DO UPDATE
+                SET fixed_at = excluded.fixed_at, fixed_in = excluded.fixed_in
+                WHERE glacier_ices.fixed_at IS NULL
+            RETURNING repo",
+            &[
+                &(issue_number as i64),
+                &file,
+                &DateTime::<FixedOffset>::from(Utc::now()),
+                &fixed_in,
+            ],
+        )
+        .await
+        .context("recording fixed glacier ice")?;
+    Ok(row.map(|row| row.get(0)))
+}
@@ -0,0 +1,92 @@
+use anyhow::Context as _;
+use chrono::{DateTime, FixedOffset};
+use tokio_postgres::Client as DbClient;
+
+/// A change to the labels of an issue, made by triagebot or seen in a webhook.
+#[derive(Debug, serde::Serialize)]
+pub struct LabelChange {
+    /// The repository, e.g. `rust-lang/rust`.
+    pub repo: String,
+    pub issue_number: u64,
+    /// The GitHub login of the user whose action caused the change.
+    pub actor: Option<String>,
+    /// The comment which triggered the change, if any.
+    pub comment_url: Option<String>,
+    /// The triagebot handler which made the change, or `None` for changes made
+    /// outside of triagebot (e.g. through the GitHub UI).
+    pub handler: Option<String>,
+    pub labels_before: Vec<String>,
+    pub labels_after: Vec<String>,
+    pub time: DateTime<FixedOffset>,
+}
+
+impl LabelChange {
+    pub fn added(&self) -> Vec<&str> {
+        self.labels_after
+            .iter()
+            .filter(|l| !self.labels_before.contains(l))
+            .map(|l| l.as_str())
+            .collect()
+    }
+
+    pub fn removed(&self) -> Vec<&str> {
+        self.labels_before
+            .iter()
+            .filter(|l| !self.labels_after.contains(l))
+            .map(|l| l.as_str())
+            .collect()
+    }
+}
+
+pub async fn record_label_change(db: &DbClient, change: &LabelChange) -> anyhow::Result<()> {
+    db.execute(
+        "INSERT INTO label_changes
+            (repo, issue_number, actor, comment_url, handler, labels_before, labels_after, time)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        &[
+            &change.repo,
+            &(change.issue_number as i64),
+            &change.actor,
+            &change.comment_url,
+            &change.handler,
+            &change.labels_before,
+            &change.labels_after,
+            &change.time,
+        ],
+    )
+    .await
+    .context("inserting label change")?;
+    Ok(())
+}
+
+/// Returns the label changes of an issue, oldest first.
+pub async fn get_label_changes(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+) -> anyhow::Result<Vec<LabelChange>> {
+    let rows = db
+        .query(
+            "SELECT actor, comment_url, handler, labels_before, labels_after, time
+            FROM label_changes
+            WHERE repo = $1 AND issue_number = $2
+            ORDER BY time ASC, change_id ASC",
+            &[&repo, &(issue_number as i64)],
+        )
+        .await
+        .context("getting label changes")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| LabelChange {
+            repo: repo.to_owned(),
+            issue_number,
+            actor: row.get(0),
+            comment_url: row.get(1),
+            handler: row.get(2),
+            labels_before: row.get(3),
+            labels_after: row.get(4),
+            time: row.get(5),
+        })
+        .collect())
+}
@@ -0,0 +1,228 @@
+use anyhow::Context as _;
+use chrono::{DateTime, FixedOffset, Utc};
+use tokio_postgres::Client as DbClient;
+
+/// The proposal was opened, and waits for a second.
+pub const PROPOSED: &str = "proposed";
+/// The proposal was seconded, and waits for the end of the waiting period.
+pub const SECONDED: &str = "seconded";
+/// The proposal was accepted.
+pub const ACCEPTED: &str = "accepted";
+/// The proposal was closed without being accepted.
+pub const CLOSED: &str = "closed";
+
+/// A major change proposal.
+#[derive(Debug)]
+pub struct MajorChange {
+    pub repo: String,
+    pub issue_number: u64,
+    /// One of `PROPOSED`, `SECONDED`, `ACCEPTED` or `CLOSED`.
+    pub state: String,
+    pub proposed_at: DateTime<FixedOffset>,
+    pub seconded_at: Option<DateTime<FixedOffset>>,
+    pub accepted_at: Option<DateTime<FixedOffset>>,
+}
+
+/// A concern raised on a major change proposal.
+#[derive(Debug)]
+pub struct Concern {
+    pub title: String,
+    pub raised_by: String,
+    pub raised_at: DateTime<FixedOffset>,
+    pub resolved_by: Option<String>,
+    pub resolved_at: Option<DateTime<FixedOffset>>,
+}
+
+fn now() -> DateTime<FixedOffset> {
+    Utc::now().into()
+}
+
+/// Records a new (or reopened) proposal, starting its lifecycle over.
+pub async fn record_proposal(db: &DbClient, repo: &str, issue_number: u64) -> anyhow::Result<()> {
+    db.execute(
+        "INSERT INTO major_changes (repo, issue_number, state, proposed_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (repo, issue_number) DO UPDATE
+            SET state = excluded.state, proposed_at = excluded.proposed_at,
+                seconded_at = NULL, accepted_at = NULL",
+        &[&repo, &(issue_number as i64), &PROPOSED, &now()],
+    )
+    .await
+    .context("inserting major change")?;
+    Ok(())
+}
+
+/// Records that a proposal was seconded, returning when it was first seconded, or `None` if the
+/// proposal is already accepted or closed.
+pub async fn record_second(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+) -> anyhow::Result<Option<DateTime<FixedOffset>>> {
+    // Proposals made before they were tracked are seconded without having been recorded.
+    let now = now();
+    let row = db
+        .query_opt(
+            "INSERT INTO major_changes (repo, issue_number, state, proposed_at, seconded_at)
+            VALUES ($1, $2, $3, $4, $4)
+            ON CONFLICT (repo, issue_number) DO UPDATE
+                SET state = excluded.state,
+                    seconded_at = COALESCE(major_changes.seconded_at, excluded.seconded_at)
+                WHERE major_changes.state IN ($5, $3)
+            RETURNING seconded_at",
+            &[&repo, &(issue_number as i64), &SECONDED, &now, &PROPOSED],
+        )
+        .await
+        .context("recording second")?;
+    Ok(row.map(|row| row.get(0)))
+}
+
+/// Moves a proposal to a final state (`ACCEPTED` or `CLOSED`).
+pub async fn finish(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+    state: &str,
+) -> anyhow::Result<()> {
+    let accepted_at = if state == ACCEPTED { Some(now()) } else { None };
+    db.execute(
+        "INSERT INTO major_changes (repo, issue_number, state, proposed_at, accepted_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (repo, issue_number) DO UPDATE
+            SET state = excluded.state, accepted_at = excluded.accepted_at",
+        &[&repo, &(issue_number as i64), &state, &now(), &accepted_at],
+    )
+    .await
+    .context("finishing major change")?;
+    Ok(())
+}
+
+fn major_change_from_row(row: tokio_postgres::Row) -> MajorChange {
+    MajorChange {
+        repo: row.get(0),
+        issue_number: row.get::<_, i64>(1) as u64,
+        state: row.get(2),
+        proposed_at: row.get(3),
+        seconded_at: row.get(4),
+        accepted_at: row.get(5),
+    }
+}
+
+/// Returns the proposal of an issue, if it is tracked.
+pub async fn get(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+) -> anyhow::Result<Option<MajorChange>> {
+    let row = db
+        .query_opt(
+            "SELECT repo, issue_number, state, proposed_at, seconded_at, accepted_at
+            FROM major_changes WHERE repo = $1 AND issue_number = $2",
+            &[&repo, &(issue_number as i64)],
+        )
+        .await
+        .context("getting major change")?;
+    Ok(row.map(major_change_from_row))
+}
+
+/// Returns the seconded proposals of all repositories.
+pub async fn seconded(db: &DbClient) -> anyhow::Result<Vec<MajorChange>> {
+    let rows = db
+        .query(
+            "SELECT repo, issue_number, state, proposed_at, seconded_at, accepted_at
+            FROM major_changes WHERE state = $1 ORDER BY seconded_at",
+            &[&SECONDED],
+        )
+        .await
+        .context("getting seconded major changes")?;
+    Ok(rows.into_iter().map(major_change_from_row).collect())
+}
+
+/// Raises a concern on a proposal. Raising a concern which is already open does nothing.
+pub async fn raise_concern(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+    title: &str,
+    raised_by: &str,
+) -> anyhow::Result<()> {
+    if open_concerns(db, repo, issue_number)
+        .await?
+        .iter()
+        .any(|c| c == title)
+    {
+        return Ok(());
+    }
+    db.execute(
+        "INSERT INTO major_change_concerns (repo, issue_number, title, raised_by, raised_at)
+        VALUES ($1, $2, $3, $4, $5)",
+        &[&repo, &(issue_number as i64), &title, &raised_by, &now()],
+    )
+    .await
+    .context("inserting concern")?;
+    Ok(())
+}
+
+/// Resolves the open concern `title` of a proposal, returning whether there was one.
+pub async fn resolve_concern(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+    title: &str,
+    resolved_by: &str,
+) -> anyhow::Result<bool> {
+    let resolved = db
+        .execute(
+            "UPDATE major_change_concerns SET resolved_by = $4, resolved_at = $5
+            WHERE repo = $1 AND issue_number = $2 AND title = $3 AND resolved_at IS NULL",
+            &[&repo, &(issue_number as i64), &title, &resolved_by, &now()],
+        )
+        .await
+        .context("resolving concern")?;
+    Ok(resolved > 0)
+}
+
+/// Returns the titles of the open concerns of a proposal.
+pub async fn open_concerns(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+) -> anyhow::Result<Vec<String>> {
+    let rows = db
+        .query(
+            "SELECT title FROM major_change_concerns
+            WHERE repo = $1 AND issue_number = $2 AND resolved_at IS NULL
+            ORDER BY raised_at",
+            &[&repo, &(issue_number as i64)],
+        )
+        .await
+        .context("getting open concerns")?;
+    Ok(rows.into_iter().map(|row| row.get(0)).collect())
+}
+
+/// Returns all concerns raised on a proposal, open or resolved, in the order they were raised.
+pub async fn concerns(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+) -> anyhow::Result<Vec<Concern>> {
+    let rows = db
+        .query(
+            "SELECT title, raised_by, raised_at, resolved_by, resolved_at
+            FROM major_change_concerns WHERE repo = $1 AND issue_number = $2
+            ORDER BY raised_at",
+            &[&repo, &(issue_number as i64)],
+        )
+        .await
+        .context("getting concerns")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| Concern {
+            title: row.get(0),
+            raised_by: row.get(1),
+            raised_at: row.get(2),
+            resolved_by: row.get(3),
+            resolved_at: row.get(4),
+        })
+        .collect())
+}
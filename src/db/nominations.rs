@@ -0,0 +1,126 @@
+use anyhow::Context as _;
+use chrono::{DateTime, FixedOffset, Utc};
+use tokio_postgres::Client as DbClient;
+
+/// The kind of a nomination for a team decision (`I-nominated`).
+pub const DECISION: &str = "decision";
+/// The kind of a nomination for a beta backport (`beta-nominated`).
+pub const BETA: &str = "beta";
+/// The kind of a nomination for a stable backport (`stable-nominated`).
+pub const STABLE: &str = "stable";
+
+/// A team nomination of an issue, e.g. `@rustbot nominate lang`.
+#[derive(Debug, serde::Serialize)]
+pub struct Nomination {
+    pub repo: String,
+    pub issue_number: u64,
+    pub team: String,
+    /// `DECISION`, `BETA` or `STABLE`.
+    pub kind: String,
+    /// The GitHub login of the nominator.
+    pub nominator: String,
+    pub reason: Option<String>,
+    pub time: DateTime<FixedOffset>,
+}
+
+pub async fn record_nomination(db: &DbClient, nomination: &Nomination) -> anyhow::Result<()> {
+    db.execute(
+        "INSERT INTO nominations (repo, issue_number, team, kind, nominator, reason, time)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        &[
+            &nomination.repo,
+            &(nomination.issue_number as i64),
+            &nomination.team,
+            &nomination.kind,
+            &nomination.nominator,
+            &nomination.reason,
+            &nomination.time,
+        ],
+    )
+    .await
+    .context("inserting nomination")?;
+    Ok(())
+}
+
+/// Marks the open nominations of an issue of `kind` (or all kinds) for `team` (or all teams) as
+/// resolved, returning the kinds of the resolved nominations.
+pub async fn resolve_nominations(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+    kind: Option<&str>,
+    team: Option<&str>,
+    resolved_by: &str,
+    resolution: &str,
+) -> anyhow::Result<Vec<String>> {
+    let rows = db
+        .query(
+            "UPDATE nominations SET resolved_by = $5, resolution = $6, resolved_at = $7
+            WHERE repo = $1 AND issue_number = $2 AND ($3::TEXT IS NULL OR kind = $3)
+                AND ($4::TEXT IS NULL OR team = $4) AND resolved_at IS NULL
+            RETURNING kind",
+            &[
+                &repo,
+                &(issue_number as i64),
+                &kind,
+                &team,
+                &resolved_by,
+                &resolution,
+                &DateTime::<FixedOffset>::from(Utc::now()),
+            ],
+        )
+        .await
+        .context("resolving nominations")?;
+    let mut kinds = rows.into_iter().map(|row| row.get(0)).collect::<Vec<_>>();
+    kinds.sort();
+    kinds.dedup();
+    Ok(kinds)
+}
+
+pub async fn has_open_nominations(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+    kind: &str,
+) -> anyhow::Result<bool> {
+    let row = db
+        .query_one(
+            "SELECT EXISTS (
+                SELECT 1 FROM nominations
+                WHERE repo = $1 AND issue_number = $2 AND kind = $3 AND resolved_at IS NULL
+            )",
+            &[&repo, &(issue_number as i64), &kind],
+        )
+        .await
+        .context("checking for open nominations")?;
+    Ok(row.get(0))
+}
+
+/// Returns the unresolved nominations in `repo`, oldest first.
+pub async fn open_nominations(db: &DbClient, repo: &str) -> anyhow::Result<Vec<Nomination>> {
+    let rows = db
+        .query(
+            "SELECT issue_number, team, kind, nominator, reason, time
+            FROM nominations
+            WHERE repo = $1 AND resolved_at IS NULL
+            ORDER BY time ASC",
+            &[&repo],
+        )
+        .await
+        .context("getting open nominations")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let issue_number: i64 = row.get(0);
+            Nomination {
+                repo: repo.to_owned(),
+                issue_number: issue_number as u64,
+                team: row.get(1),
+                kind: row.get(2),
+                nominator: row.get(3),
+                reason: row.get(4),
+                time: row.get(5),
+            }
+        })
+        .collect())
+}
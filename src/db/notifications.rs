@@ -1,5 +1,5 @@
 use anyhow::Context as _;
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, Utc};
 use tokio_postgres::Client as DbClient;
 
 pub struct Notification {
@@ -54,7 +54,7 @@ pub async fn delete_ping(
             let rows = db
                 .query(
                     "DELETE FROM notifications WHERE user_id = $1 and origin_url = $2
-                    RETURNING origin_html, time, short_description, metadata",
+                    RETURNING origin_html, time, short_description, metadata, snoozed_until, remind_at",
                     &[&user_id, &origin_url],
                 )
                 .await
@@ -66,12 +66,16 @@ pub async fn delete_ping(
                     let time: DateTime<FixedOffset> = row.get(1);
                     let short_description: Option<String> = row.get(2);
                     let metadata: Option<String> = row.get(3);
+                    let snoozed_until: Option<DateTime<FixedOffset>> = row.get(4);
+                    let remind_at: Option<DateTime<FixedOffset>> = row.get(5);
                     NotificationData {
                         origin_url: origin_url.to_owned(),
                         origin_text,
                         time,
                         short_description,
                         metadata,
+                        snoozed_until,
+                        remind_at,
                     }
                 })
                 .collect())
@@ -103,7 +107,8 @@ pub async fn delete_ping(
             let row = t
                 .query_one(
                     "DELETE FROM notifications WHERE notification_id = $1
-                RETURNING origin_url, origin_html, time, short_description, metadata",
+                RETURNING origin_url, origin_html, time, short_description, metadata,
+                    snoozed_until, remind_at",
                     &[&notification_id],
                 )
                 .await
@@ -117,12 +122,16 @@ pub async fn delete_ping(
             let time: DateTime<FixedOffset> = row.get(2);
             let short_description: Option<String> = row.get(3);
             let metadata: Option<String> = row.get(4);
+            let snoozed_until: Option<DateTime<FixedOffset>> = row.get(5);
+            let remind_at: Option<DateTime<FixedOffset>> = row.get(6);
             let deleted_notification = NotificationData {
                 origin_url,
                 origin_text,
                 time,
                 short_description,
                 metadata,
+                snoozed_until,
+                remind_at,
             };
 
             if let Err(e) = t.commit().await {
@@ -142,7 +151,8 @@ pub async fn delete_ping(
             let rows = db
                 .query(
                     "DELETE FROM notifications WHERE user_id = $1
-                        RETURNING origin_url, origin_html, time, short_description, metadata",
+                        RETURNING origin_url, origin_html, time, short_description, metadata,
+                        snoozed_until, remind_at",
                     &[&user_id],
                 )
                 .await
@@ -155,12 +165,16 @@ pub async fn delete_ping(
                     let time: DateTime<FixedOffset> = row.get(2);
                     let short_description: Option<String> = row.get(3);
                     let metadata: Option<String> = row.get(4);
+                    let snoozed_until: Option<DateTime<FixedOffset>> = row.get(5);
+                    let remind_at: Option<DateTime<FixedOffset>> = row.get(6);
                     NotificationData {
                         origin_url,
                         origin_text,
                         time,
                         short_description,
                         metadata,
+                        snoozed_until,
+                        remind_at,
                     }
                 })
                 .collect())
@@ -175,6 +189,17 @@ pub struct NotificationData {
     pub short_description: Option<String>,
     pub time: DateTime<FixedOffset>,
     pub metadata: Option<String>,
+    /// The notification is hidden from the listing until then.
+    pub snoozed_until: Option<DateTime<FixedOffset>>,
+    /// The user is sent a message on Zulip about the notification then.
+    pub remind_at: Option<DateTime<FixedOffset>>,
+}
+
+impl NotificationData {
+    pub fn is_snoozed(&self, now: DateTime<Utc>) -> bool {
+        self.snoozed_until
+            .map_or(false, |until| until.with_timezone(&Utc) > now)
+    }
 }
 
 pub async fn move_indices(
@@ -317,18 +342,29 @@ pub async fn add_metadata(
     Ok(())
 }
 
+/// The order of the notification listing, to which the indexes of the Zulip commands refer.
+///
+/// New notifications have no `idx`; their ID breaks the tie, so that every query sees them in the
+/// same order.
+const LISTING_ORDER: &str = "notifications.idx ASC NULLS LAST, notifications.notification_id ASC";
+
 pub async fn get_notifications(
     db: &DbClient,
     username: &str,
 ) -> anyhow::Result<Vec<NotificationData>> {
     let notifications = db
         .query(
-            "
-        select username, origin_url, origin_html, time, short_description, idx, metadata
+            format!(
+                "
+        select username, origin_url, origin_html, time, short_description, idx, metadata,
+            snoozed_until, remind_at
         from notifications
         join users on notifications.user_id = users.user_id
         where username = $1
-        order by notifications.idx asc nulls last;",
+        order by {};",
+                LISTING_ORDER
+            )
+            .as_str(),
             &[&username],
         )
         .await
@@ -341,6 +377,8 @@ pub async fn get_notifications(
         let time: DateTime<FixedOffset> = notification.get(3);
         let short_description: Option<String> = notification.get(4);
         let metadata: Option<String> = notification.get(6);
+        let snoozed_until: Option<DateTime<FixedOffset>> = notification.get(7);
+        let remind_at: Option<DateTime<FixedOffset>> = notification.get(8);
 
         data.push(NotificationData {
             origin_url,
@@ -348,8 +386,105 @@ pub async fn get_notifications(
             short_description,
             time,
             metadata,
+            snoozed_until,
+            remind_at,
         });
     }
 
     Ok(data)
 }
+
+/// Hides the notification at the 0-based `idx` until `until`.
+pub async fn snooze(
+    db: &DbClient,
+    user_id: i64,
+    idx: usize,
+    until: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    set_time(db, user_id, idx, "snoozed_until", until).await
+}
+
+/// Schedules a reminder of the notification at the 0-based `idx` at `at`.
+pub async fn remind(
+    db: &DbClient,
+    user_id: i64,
+    idx: usize,
+    at: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    set_time(db, user_id, idx, "remind_at", at).await
+}
+
+async fn set_time(
+    db: &DbClient,
+    user_id: i64,
+    idx: usize,
+    column: &'static str,
+    time: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    // Indexes are positions in the listing of all the notifications of the user, snoozed or not.
+    let updated = db
+        .execute(
+            format!(
+                "UPDATE notifications SET {} = $3
+                WHERE notification_id = (
+                    SELECT notification_id FROM notifications
+                    WHERE user_id = $1
+                    ORDER BY {}
+                    OFFSET $2 LIMIT 1
+                )",
+                column, LISTING_ORDER
+            )
+            .as_str(),
+            &[&user_id, &(idx as i64), &time],
+        )
+        .await
+        .with_context(|| format!("setting {}", column))?;
+    if updated == 0 {
+        anyhow::bail!("No such notification with index {}", idx + 1);
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Reminder {
+    pub notification_id: i64,
+    pub user_id: i64,
+    pub origin_url: String,
+    pub short_description: Option<String>,
+    pub metadata: Option<String>,
+}
+
+/// Returns the reminders which are due.
+pub async fn due_reminders(db: &DbClient) -> anyhow::Result<Vec<Reminder>> {
+    let rows = db
+        .query(
+            "SELECT notification_id, user_id, origin_url, short_description, metadata
+            FROM notifications
+            WHERE remind_at <= now()
+            ORDER BY remind_at",
+            &[],
+        )
+        .await
+        .context("getting due reminders")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| Reminder {
+            notification_id: row.get(0),
+            user_id: row.get(1),
+            origin_url: row.get(2),
+            short_description: row.get(3),
+            metadata: row.get(4),
+        })
+        .collect())
+}
+
+/// Records that a reminder was sent.
+pub async fn clear_reminder(db: &DbClient, notification_id: i64) -> anyhow::Result<()> {
+    db.execute(
+        "UPDATE notifications SET remind_at = NULL WHERE notification_id = $1",
+        &[&notification_id],
+    )
+    .await
+    .context("clearing reminder")?;
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use anyhow::Context as _;
+use chrono::{DateTime, FixedOffset, Utc};
+use tokio_postgres::Client as DbClient;
+
+/// Records that `team` was pinged on an issue.
+pub async fn record_team_ping(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+    team: &str,
+) -> anyhow::Result<()> {
+    db.execute(
+        "INSERT INTO team_pings (repo, issue_number, team, time) VALUES ($1, $2, $3, $4)",
+        &[
+            &repo,
+            &(issue_number as i64),
+            &team,
+            &DateTime::<FixedOffset>::from(Utc::now()),
+        ],
+    )
+    .await
+    .context("inserting team ping")?;
+    Ok(())
+}
+
+/// Returns when `team` was last pinged on an issue, if ever.
+pub async fn last_team_ping(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+    team: &str,
+) -> anyhow::Result<Option<DateTime<FixedOffset>>> {
+    let row = db
+        .query_one(
+            "SELECT max(time) FROM team_pings WHERE repo = $1 AND issue_number = $2 AND team = $3",
+            &[&repo, &(issue_number as i64), &team],
+        )
+        .await
+        .context("getting last team ping")?;
+    Ok(row.get(0))
+}
+
+/// Returns how often `team` was pinged since `since`, across all issues.
+pub async fn team_pings_since(
+    db: &DbClient,
+    team: &str,
+    since: DateTime<FixedOffset>,
+) -> anyhow::Result<u32> {
+    let row = db
+        .query_one(
+            "SELECT count(*) FROM team_pings WHERE team = $1 AND time >= $2",
+            &[&team, &since],
+        )
+        .await
+        .context("counting team pings")?;
+    let count: i64 = row.get(0);
+    Ok(count as u32)
+}
+
+pub async fn opt_out(db: &DbClient, user_id: i64, team: &str) -> anyhow::Result<()> {
+    db.execute(
+        "INSERT INTO ping_opt_outs (user_id, team) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        &[&user_id, &team],
+    )
+    .await
+    .context("inserting ping opt-out")?;
+    Ok(())
+}
+
+/// Returns whether the user was opted out.
+pub async fn opt_in(db: &DbClient, user_id: i64, team: &str) -> anyhow::Result<bool> {
+    let deleted = db
+        .execute(
+            "DELETE FROM ping_opt_outs WHERE user_id = $1 AND team = $2",
+            &[&user_id, &team],
+        )
+        .await
+        .context("deleting ping opt-out")?;
+    Ok(deleted > 0)
+}
+
+/// Returns the GitHub IDs of the users who opted out of pings to `team`.
+pub async fn opted_out_users(db: &DbClient, team: &str) -> anyhow::Result<Vec<i64>> {
+    let rows = db
+        .query(
+            "SELECT user_id FROM ping_opt_outs WHERE team = $1",
+            &[&team],
+        )
+        .await
+        .context("getting ping opt-outs")?;
+    Ok(rows.into_iter().map(|row| row.get(0)).collect())
+}
+
+pub async fn subscribe(
+    db: &DbClient,
+    user_id: i64,
+    username: &str,
+    team: &str,
+) -> anyhow::Result<()> {
+    db.execute(
+        "INSERT INTO ping_subscriptions (user_id, username, team) VALUES ($1, $2, $3)
+        ON CONFLICT (user_id, team) DO UPDATE SET username = EXCLUDED.username",
+        &[&user_id, &username, &team],
+    )
+    .await
+    .context("inserting ping subscription")?;
+    Ok(())
+}
+
+/// Returns whether the user was subscribed.
+pub async fn unsubscribe(db: &DbClient, user_id: i64, team: &str) -> anyhow::Result<bool> {
+    let deleted = db
+        .execute(
+            "DELETE FROM ping_subscriptions WHERE user_id = $1 AND team = $2",
+            &[&user_id, &team],
+        )
+        .await
+        .context("deleting ping subscription")?;
+    Ok(deleted > 0)
+}
+
+/// Returns the GitHub usernames of the users subscribed to pings of `team`.
+pub async fn subscribers(db: &DbClient, team: &str) -> anyhow::Result<Vec<String>> {
+    let rows = db
+        .query(
+            "SELECT username FROM ping_subscriptions WHERE team = $1 ORDER BY username",
+            &[&team],
+        )
+        .await
+        .context("getting ping subscriptions")?;
+    Ok(rows.into_iter().map(|row| row.get(0)).collect())
+}
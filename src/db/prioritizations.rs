@@ -0,0 +1,92 @@
+use anyhow::Context as _;
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use tokio_postgres::Client as DbClient;
+
+/// Records that an issue was requested to be prioritized.
+///
+/// A request for an issue which is still waiting for its priority keeps the time of the first
+/// request; a request for an issue which was already prioritized starts over.
+pub async fn record_request(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+    requested_by: &str,
+) -> anyhow::Result<()> {
+    db.execute(
+        "INSERT INTO prioritizations (repo, issue_number, requested_by, requested_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (repo, issue_number) DO UPDATE
+            SET requested_by = excluded.requested_by, requested_at = excluded.requested_at,
+                priority = NULL, prioritized_at = NULL
+            WHERE prioritizations.prioritized_at IS NOT NULL",
+        &[
+            &repo,
+            &(issue_number as i64),
+            &requested_by,
+            &DateTime::<FixedOffset>::from(Utc::now()),
+        ],
+    )
+    .await
+    .context("inserting prioritization request")?;
+    Ok(())
+}
+
+/// Records that an issue was given `priority`, returning how long it took since it was requested
+/// to be prioritized, if it was.
+pub async fn record_prioritized(
+    db: &DbClient,
+    repo: &str,
+    issue_number: u64,
+    priority: &str,
+) -> anyhow::Result<Option<Duration>> {
+    let now = DateTime::<FixedOffset>::from(Utc::now());
+    let row = db
+        .query_opt(
+            "UPDATE prioritizations SET priority = $3, prioritized_at = $4
+            WHERE repo = $1 AND issue_number = $2 AND prioritized_at IS NULL
+            RETURNING requested_at",
+            &[&repo, &(issue_number as i64), &priority, &now],
+        )
+        .await
+        .context("recording prioritization")?;
+    Ok(row.map(|row| now - row.get::<_, DateTime<FixedOffset>>(0)))
+}
+
+/// The prioritization of an issue.
+#[derive(Debug, serde::Serialize)]
+pub struct Prioritization {
+    pub issue_number: u64,
+    pub requested_by: String,
+    pub requested_at: DateTime<FixedOffset>,
+    pub priority: Option<String>,
+    pub prioritized_at: Option<DateTime<FixedOffset>>,
+    /// How long the prioritization took, once the issue was prioritized.
+    pub hours_to_prioritize: Option<i64>,
+}
+
+/// Returns the prioritizations of a repository, most recently requested first.
+pub async fn get_prioritizations(db: &DbClient, repo: &str) -> anyhow::Result<Vec<Prioritization>> {
+    let rows = db
+        .query(
+            "SELECT issue_number, requested_by, requested_at, priority, prioritized_at
+            FROM prioritizations WHERE repo = $1 ORDER BY requested_at DESC",
+            &[&repo],
+        )
+        .await
+        .context("getting prioritizations")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let requested_at: DateTime<FixedOffset> = row.get(2);
+            let prioritized_at: Option<DateTime<FixedOffset>> = row.get(4);
+            Prioritization {
+                issue_number: row.get::<_, i64>(0) as u64,
+                requested_by: row.get(1),
+                requested_at,
+                priority: row.get(3),
+                prioritized_at,
+                hours_to_prioritize: prioritized_at.map(|at| (at - requested_at).num_hours()),
+            }
+        })
+        .collect())
+}
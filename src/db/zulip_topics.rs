@@ -0,0 +1,48 @@
+use anyhow::Context as _;
+use chrono::{DateTime, FixedOffset, Utc};
+use tokio_postgres::Client as DbClient;
+
+/// Links a Zulip topic to the issue it discusses; the topic must be unique to the issue, like
+/// `Issue::zulip_topic`.
+///
+/// Returns whether the topic was not linked yet; a topic stays linked to its first issue.
+pub async fn link(
+    db: &DbClient,
+    stream_id: u64,
+    topic: &str,
+    repo: &str,
+    issue_number: u64,
+) -> anyhow::Result<bool> {
+    let inserted = db
+        .execute(
+            "INSERT INTO zulip_topics (stream_id, topic, repo, issue_number, linked_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT DO NOTHING",
+            &[
+                &(stream_id as i64),
+                &topic,
+                &repo,
+                &(issue_number as i64),
+                &DateTime::<FixedOffset>::from(Utc::now()),
+            ],
+        )
+        .await
+        .context("linking zulip topic")?;
+    Ok(inserted == 1)
+}
+
+/// Returns the repository and number of the issue linked to a Zulip topic.
+pub async fn linked_issue(
+    db: &DbClient,
+    stream_id: u64,
+    topic: &str,
+) -> anyhow::Result<Option<(String, u64)>> {
+    let row = db
+        .query_opt(
+            "SELECT repo, issue_number FROM zulip_topics WHERE stream_id = $1 AND topic = $2",
+            &[&(stream_id as i64), &topic],
+        )
+        .await
+        .context("getting linked issue")?;
+    Ok(row.map(|row| (row.get(0), row.get::<_, i64>(1) as u64)))
+}
@@ -1,5 +1,9 @@
 use anyhow::Context;
 
+use crate::db::{
+    label_changes::{record_label_change, LabelChange},
+    DbClient,
+};
 use chrono::{DateTime, FixedOffset, Utc};
 use futures::stream::{FuturesUnordered, StreamExt};
 use futures::{future::BoxFuture, FutureExt};
@@ -12,7 +16,7 @@ use std::{
     time::{Duration, SystemTime},
 };
 
-#[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, serde::Deserialize)]
 pub struct User {
     pub login: String,
     pub id: Option<i64>,
@@ -168,6 +172,29 @@ impl GithubClient {
         let (resp, req_dbg) = self._send_req(req).await?;
         Ok(resp.json().await.context(req_dbg)?)
     }
+
+    /// Fetches every page of the list returned by `url`.
+    pub async fn json_paginated<T>(&self, url: &str) -> anyhow::Result<Vec<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        const PER_PAGE: usize = 100;
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let mut items = Vec::new();
+        for page in 1.. {
+            let page_url = format!("{}{}per_page={}&page={}", url, separator, PER_PAGE, page);
+            let mut page: Vec<T> = self
+                .json(self.get(&page_url))
+                .await
+                .with_context(|| format!("failed to list {}", page_url))?;
+            let last = page.len() < PER_PAGE;
+            items.append(&mut page);
+            if last {
+                break;
+            }
+        }
+        Ok(items)
+    }
 }
 
 impl User {
@@ -196,6 +223,17 @@ impl User {
         Ok(in_all || is_triager || is_pri_member)
     }
 
+    /// Returns the names of all teams in the team repository that this user is a member of.
+    pub async fn teams<'a>(&'a self, client: &'a GithubClient) -> anyhow::Result<Vec<String>> {
+        let permission = crate::team_data::teams(client).await?;
+        Ok(permission
+            .teams
+            .into_iter()
+            .filter(|(_, team)| team.members.iter().any(|m| m.github == self.login))
+            .map(|(name, _)| name)
+            .collect())
+    }
+
     // Returns the ID of the given user, if the user is in the `all` team.
     pub async fn get_id<'a>(&'a self, client: &'a GithubClient) -> anyhow::Result<Option<usize>> {
         let permission = crate::team_data::teams(client).await?;
@@ -206,6 +244,23 @@ impl User {
             .find(|g| g.github == self.login)
             .map(|u| u.github_id))
     }
+
+    /// Returns the ID of the given user, asking GitHub when the user is not in the `all` team.
+    pub async fn get_github_id(&self, client: &GithubClient) -> anyhow::Result<i64> {
+        if let Some(id) = self.get_id(client).await? {
+            return Ok(id as i64);
+        }
+        #[derive(serde::Deserialize)]
+        struct UserId {
+            id: i64,
+        }
+        let url = format!("{}/users/{}", Repository::GITHUB_API_URL, self.login);
+        let user: UserId = client
+            .json(client.get(&url))
+            .await
+            .with_context(|| format!("getting the id of {}", self.login))?;
+        Ok(user.id)
+    }
 }
 
 pub async fn get_team(
@@ -222,26 +277,108 @@ pub struct Label {
     pub name: String,
 }
 
-impl Label {
-    async fn exists<'a>(&'a self, repo_api_prefix: &'a str, client: &'a GithubClient) -> bool {
-        #[allow(clippy::redundant_pattern_matching)]
-        let url = format!("{}/labels/{}", repo_api_prefix, self.name);
-        match client.send_req(client.get(&url)).await {
-            Ok(_) => true,
-            // XXX: Error handling if the request failed for reasons beyond 'label didn't exist'
-            Err(_) => false,
+/// A label as defined in a repository, as opposed to one applied to an issue.
+#[derive(PartialEq, Eq, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RepositoryLabel {
+    pub name: String,
+    pub color: String,
+    pub description: Option<String>,
+}
+
+/// Who or what is changing the labels of an issue, recorded in the label audit log.
+#[derive(Debug, Clone, Copy)]
+pub struct LabelChangeOrigin<'a> {
+    /// The handler making the change, e.g. `relabel`.
+    pub handler: &'a str,
+    /// The GitHub user whose action triggered the change.
+    pub actor: Option<&'a str>,
+    /// The comment which triggered the change, if any.
+    pub comment_url: Option<&'a str>,
+}
+
+impl<'a> LabelChangeOrigin<'a> {
+    pub fn from_event(handler: &'a str, event: &'a Event) -> LabelChangeOrigin<'a> {
+        let actor = match event {
+            Event::Issue(e) => &e.sender,
+            _ => event.user(),
+        };
+        let comment_url = match event {
+            Event::IssueComment(e) => Some(e.comment.html_url.as_str()),
+            _ => None,
+        };
+        LabelChangeOrigin {
+            handler,
+            actor: Some(&actor.login),
+            comment_url,
+        }
+    }
+
+    pub fn from_issues_event(handler: &'a str, event: &'a IssuesEvent) -> LabelChangeOrigin<'a> {
+        LabelChangeOrigin {
+            handler,
+            actor: Some(&event.sender.login),
+            comment_url: None,
         }
     }
 }
 
-#[derive(Debug, serde::Deserialize)]
+/// Returned by `Issue::set_labels` when some of the labels neither exist in
+/// the repository nor are declared in the `[labels]` configuration, in which
+/// case no label is changed.
+#[derive(Debug)]
+pub struct UnknownLabels {
+    pub labels: Vec<String>,
+}
+
+impl fmt::Display for UnknownLabels {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let labels = self
+            .labels
+            .iter()
+            .map(|l| format!("`{}`", l))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(
+            f,
+            "Unknown labels: {}. Labels must exist in the repository, or be declared in the \
+             `[labels]` section of `triagebot.toml` to be created automatically.",
+            labels
+        )
+    }
+}
+
+impl std::error::Error for UnknownLabels {}
+
+fn is_not_found(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        .map_or(false, |status| status == StatusCode::NOT_FOUND)
+}
+
+fn is_conflict(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        .map_or(false, |status| status == StatusCode::CONFLICT)
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct PullRequestDetails {
     // none for now
 }
 
+/// A file changed by a pull request.
 #[derive(Debug, serde::Deserialize)]
+pub struct PullRequestFile {
+    pub filename: String,
+    /// The path of the file before it was renamed, if it was.
+    #[serde(default)]
+    pub previous_filename: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct Issue {
     pub number: u64,
+    #[serde(deserialize_with = "opt_string")]
     pub body: String,
     created_at: chrono::DateTime<Utc>,
     #[serde(default)]
@@ -251,6 +388,9 @@ pub struct Issue {
     pub user: User,
     pub labels: Vec<Label>,
     pub assignees: Vec<User>,
+    /// The relationship of the author to the repository, e.g. `FIRST_TIME_CONTRIBUTOR`.
+    #[serde(default)]
+    pub author_association: Option<String>,
     pub pull_request: Option<PullRequestDetails>,
     #[serde(default)]
     pub merged: bool,
@@ -262,12 +402,16 @@ pub struct Issue {
 
 #[derive(Debug, serde::Deserialize)]
 pub struct Comment {
+    pub id: usize,
     #[serde(deserialize_with = "opt_string")]
     pub body: String,
     pub html_url: String,
     pub user: User,
     #[serde(alias = "submitted_at")] // for pull request reviews
     pub updated_at: chrono::DateTime<Utc>,
+    /// The state of pull request reviews, e.g. `changes_requested`.
+    #[serde(default)]
+    pub state: Option<String>,
 }
 
 fn opt_string<'de, D>(deserializer: D) -> Result<String, D::Error>
@@ -305,7 +449,7 @@ impl fmt::Display for AssignmentError {
 
 impl std::error::Error for AssignmentError {}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IssueRepository {
     pub organization: String,
     pub repository: String,
@@ -340,6 +484,25 @@ impl Issue {
         }
     }
 
+    /// The Zulip topic of this issue: its title followed by its topic reference.
+    pub fn zulip_topic(&self) -> String {
+        // Concatenate the issue title and the topic reference, truncating such that
+        // the overall length does not exceed 60 characters (a Zulip limitation).
+        let topic_ref = self.zulip_topic_reference();
+        // Skip chars until the last characters that can be written:
+        // Maximum 60, minus the reference, minus the elipsis and the space
+        let mut chars = self
+            .title
+            .char_indices()
+            .skip(60 - topic_ref.chars().count() - 2);
+        match chars.next() {
+            Some((len, _)) if chars.next().is_some() => {
+                format!("{}… {}", &self.title[..len], topic_ref)
+            }
+            _ => format!("{} {}", self.title, topic_ref),
+        }
+    }
+
     pub fn repository(&self) -> &IssueRepository {
         self.repository.get_or_init(|| {
             // https://api.github.com/repos/rust-lang/rust/issues/69257/comments
@@ -366,12 +529,38 @@ impl Issue {
         self.pull_request.is_some()
     }
 
+    /// The files changed by this pull request; empty for issues.
+    pub async fn files(&self, client: &GithubClient) -> anyhow::Result<Vec<PullRequestFile>> {
+        if !self.is_pr() {
+            return Ok(Vec::new());
+        }
+        let url = format!("{}/pulls/{}/files", self.repository().url(), self.number);
+        client.json_paginated(&url).await
+    }
+
     pub async fn get_comment(&self, client: &GithubClient, id: usize) -> anyhow::Result<Comment> {
         let comment_url = format!("{}/issues/comments/{}", self.repository().url(), id);
         let comment = client.json(client.get(&comment_url)).await?;
         Ok(comment)
     }
 
+    pub async fn close(&self, client: &GithubClient) -> anyhow::Result<()> {
+        let edit_url = format!("{}/issues/{}", self.repository().url(), self.number);
+        #[derive(serde::Serialize)]
+        struct CloseIssue<'a> {
+            state: &'a str,
+        }
+        client
+            ._send_req(
+                client
+                    .patch(&edit_url)
+                    .json(&CloseIssue { state: "closed" }),
+            )
+            .await
+            .context("failed to close issue")?;
+        Ok(())
+    }
+
     pub async fn edit_body(&self, client: &GithubClient, body: &str) -> anyhow::Result<()> {
         let edit_url = format!("{}/issues/{}", self.repository().url(), self.number);
         #[derive(serde::Serialize)]
@@ -419,11 +608,43 @@ impl Issue {
         Ok(())
     }
 
+    /// Posts a comment, returning it (e.g. to edit it later).
+    pub async fn create_comment(
+        &self,
+        client: &GithubClient,
+        body: &str,
+    ) -> anyhow::Result<Comment> {
+        #[derive(serde::Serialize)]
+        struct PostComment<'a> {
+            body: &'a str,
+        }
+        client
+            .json(client.post(&self.comments_url).json(&PostComment { body }))
+            .await
+            .context("failed to post comment")
+    }
+
+    /// Replaces the labels of this issue, recording the change in the label audit log.
+    ///
+    /// Fails with `UnknownLabels`, changing nothing, if any of the labels can't be applied.
     pub async fn set_labels(
         &self,
         client: &GithubClient,
+        db: &DbClient,
+        origin: LabelChangeOrigin<'_>,
         labels: Vec<Label>,
     ) -> anyhow::Result<()> {
+        // Labels of exclusive groups (e.g. `P-*`) replace the ones already
+        // present, regardless of which handler is setting them.
+        let config = crate::config::get(client, &self.repository().to_string())
+            .await
+            .ok();
+        let labels = match config.as_ref().and_then(|c| c.exclusive_labels.as_ref()) {
+            Some(exclusive) => exclusive.resolve(&self.labels, labels),
+            None => labels,
+        };
+        let definitions = config.as_ref().and_then(|c| c.labels.as_ref());
+
         log::info!("set_labels {} to {:?}", self.global_id(), labels);
         // PUT /repos/:owner/:repo/issues/:number/labels
         // repo_url = https://api.github.com/repos/Codertocat/Hello-World
@@ -433,13 +654,34 @@ impl Issue {
             number = self.number
         );
 
+        let repo = Repository {
+            full_name: self.repository().to_string(),
+        };
         let mut stream = labels
-            .into_iter()
-            .map(|label| async { (label.exists(&self.repository().url(), &client).await, label) })
+            .iter()
+            .map(|label| async {
+                let existing = repo.get_label(client, &label.name).await;
+                (existing, label)
+            })
             .collect::<FuturesUnordered<_>>();
-        let mut labels = Vec::new();
-        while let Some((true, label)) = stream.next().await {
-            labels.push(label);
+        let mut missing = Vec::new();
+        let mut unknown = Vec::new();
+        while let Some((existing, label)) = stream.next().await {
+            if existing?.is_some() {
+                continue;
+            }
+            match definitions.and_then(|d| d.definition(&label.name)) {
+                Some(definition) => missing.push(definition),
+                None => unknown.push(label.name.clone()),
+            }
+        }
+        // Nothing is changed unless every label can be applied.
+        if !unknown.is_empty() {
+            return Err(UnknownLabels { labels: unknown }.into());
+        }
+        for definition in missing {
+            log::info!("creating label {} in {}", definition.name, repo.full_name);
+            repo.create_label(client, &definition).await?;
         }
 
         #[derive(serde::Serialize)]
@@ -453,6 +695,24 @@ impl Issue {
             .await
             .context("failed to set labels")?;
 
+        let change = LabelChange {
+            repo: repo.full_name.clone(),
+            issue_number: self.number,
+            actor: origin.actor.map(|a| a.to_owned()),
+            comment_url: origin.comment_url.map(|c| c.to_owned()),
+            handler: Some(origin.handler.to_owned()),
+            labels_before: self.labels.iter().map(|l| l.name.clone()).collect(),
+            labels_after: labels.iter().map(|l| l.name.clone()).collect(),
+            time: Utc::now().into(),
+        };
+        if let Err(e) = record_label_change(db, &change).await {
+            log::error!(
+                "failed to record label change on {}: {:?}",
+                self.global_id(),
+                e
+            );
+        }
+
         Ok(())
     }
 
@@ -631,6 +891,7 @@ pub struct PullRequestReviewEvent {
     pub review: Comment,
     pub changes: Option<Changes>,
     pub repository: Repository,
+    pub sender: User,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -641,6 +902,7 @@ pub struct PullRequestReviewComment {
     pub issue: Issue,
     pub comment: Comment,
     pub repository: Repository,
+    pub sender: User,
 }
 
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
@@ -658,6 +920,8 @@ pub struct IssueCommentEvent {
     pub issue: Issue,
     pub comment: Comment,
     pub repository: Repository,
+    /// The user who made the change, which for edits may not be the author of the comment.
+    pub sender: User,
 }
 
 #[derive(PartialEq, Eq, Debug, serde::Deserialize)]
@@ -684,6 +948,12 @@ pub enum IssuesAction {
     ReadyForReview,
     Synchronize,
     ConvertedToDraft,
+    /// Not sent by GitHub: a review was submitted on the pull request, with the state in
+    /// `IssuesEvent::review_state`. It goes through every handler of issue events like the
+    /// actions GitHub sends, but only `autolabel` (`trigger-reviews`) and `notify_zulip`
+    /// (`message_on_review`) are expected to react to it; the others match the actions they
+    /// handle, and ignore it.
+    ReviewSubmitted,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -695,6 +965,10 @@ pub struct IssuesEvent {
     pub repository: Repository,
     /// Some if action is IssuesAction::Labeled, for example
     pub label: Option<Label>,
+    pub sender: User,
+    /// Some if action is IssuesAction::ReviewSubmitted
+    #[serde(skip)]
+    pub review_state: Option<String>,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -704,7 +978,7 @@ pub struct IssueSearchResult {
     pub items: Vec<Issue>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct Repository {
     pub full_name: String,
 }
@@ -752,6 +1026,146 @@ impl Repository {
         }
     }
 
+    pub async fn get_issue(&self, client: &GithubClient, number: u64) -> anyhow::Result<Issue> {
+        let url = format!(
+            "{}/repos/{}/issues/{}",
+            Repository::GITHUB_API_URL,
+            self.full_name,
+            number
+        );
+        client
+            .json(client.get(&url))
+            .await
+            .with_context(|| format!("failed to get issue {}#{}", self.full_name, number))
+    }
+
+    /// Returns the name of the default branch, e.g. `master`.
+    pub async fn default_branch(&self, client: &GithubClient) -> anyhow::Result<String> {
+        #[derive(serde::Deserialize)]
+        struct RepositoryDetails {
+            default_branch: String,
+        }
+        let url = format!("{}/repos/{}", Repository::GITHUB_API_URL, self.full_name);
+        let details: RepositoryDetails = client
+            .json(client.get(&url))
+            .await
+            .with_context(|| format!("failed to get repository {}", self.full_name))?;
+        Ok(details.default_branch)
+    }
+
+    /// Merges `head` into the `base` branch, returning the tree of the merge commit, or `None` if
+    /// they conflict.
+    pub async fn merge(
+        &self,
+        client: &GithubClient,
+        base: &str,
+        head: &str,
+        message: &str,
+    ) -> anyhow::Result<Option<String>> {
+        #[derive(serde::Deserialize)]
+        struct MergeCommit {
+            commit: MergeCommitDetails,
+        }
+        #[derive(serde::Deserialize)]
+        struct MergeCommitDetails {
+            tree: MergeTree,
+        }
+        #[derive(serde::Deserialize)]
+        struct MergeTree {
+            sha: String,
+        }
+        let url = format!(
+            "{}/repos/{}/merges",
+            Repository::GITHUB_API_URL,
+            self.full_name
+        );
+        let req = client.post(&url).json(&serde_json::json!({
+            "base": base,
+            "head": head,
+            "commit_message": message,
+        }));
+        match client.json::<MergeCommit>(req).await {
+            Ok(merge) => Ok(Some(merge.commit.tree.sha)),
+            Err(e) if is_conflict(&e) => Ok(None),
+            Err(e) => Err(e.context(format!("failed to merge {} into {}", head, base))),
+        }
+    }
+
+    fn label_url(&self, name: &str) -> String {
+        let mut url = url::Url::parse(&format!(
+            "{}/repos/{}/labels",
+            Repository::GITHUB_API_URL,
+            self.full_name
+        ))
+        .unwrap();
+        // Label names may contain spaces, slashes, etc.
+        url.path_segments_mut().unwrap().push(name);
+        url.to_string()
+    }
+
+    pub async fn get_label(
+        &self,
+        client: &GithubClient,
+        name: &str,
+    ) -> anyhow::Result<Option<RepositoryLabel>> {
+        match client.json(client.get(&self.label_url(name))).await {
+            Ok(label) => Ok(Some(label)),
+            Err(e) if is_not_found(&e) => Ok(None),
+            Err(e) => Err(e.context(format!("failed to get label {}", name))),
+        }
+    }
+
+    pub async fn create_label(
+        &self,
+        client: &GithubClient,
+        label: &RepositoryLabel,
+    ) -> anyhow::Result<()> {
+        let url = format!(
+            "{}/repos/{}/labels",
+            Repository::GITHUB_API_URL,
+            self.full_name
+        );
+        client
+            ._send_req(client.post(&url).json(label))
+            .await
+            .with_context(|| format!("failed to create label {}", label.name))?;
+        Ok(())
+    }
+
+    /// Updates the label `name` to match `label`, renaming it if the names differ.
+    ///
+    /// Renaming a label keeps it applied to the issues it was applied to.
+    pub async fn update_label(
+        &self,
+        client: &GithubClient,
+        name: &str,
+        label: &RepositoryLabel,
+    ) -> anyhow::Result<()> {
+        #[derive(serde::Serialize)]
+        struct UpdateLabel<'a> {
+            new_name: &'a str,
+            color: &'a str,
+            description: Option<&'a str>,
+        }
+        client
+            ._send_req(client.patch(&self.label_url(name)).json(&UpdateLabel {
+                new_name: &label.name,
+                color: &label.color,
+                description: label.description.as_deref(),
+            }))
+            .await
+            .with_context(|| format!("failed to update label {}", name))?;
+        Ok(())
+    }
+
+    pub async fn delete_label(&self, client: &GithubClient, name: &str) -> anyhow::Result<()> {
+        client
+            ._send_req(client.delete(&self.label_url(name)))
+            .await
+            .with_context(|| format!("failed to delete label {}", name))?;
+        Ok(())
+    }
+
     pub async fn get_issues_count<'a>(
         &self,
         client: &GithubClient,
@@ -865,8 +1279,38 @@ pub struct CreateEvent {
 pub struct PushEvent {
     #[serde(rename = "ref")]
     pub git_ref: String,
-    repository: Repository,
-    sender: User,
+    /// The commit the ref pointed to before the push.
+    pub before: String,
+    /// The commit the ref points to after the push.
+    pub after: String,
+    #[serde(default)]
+    pub commits: Vec<PushCommit>,
+    pub repository: Repository,
+    pub sender: User,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct PushCommit {
+    pub id: String,
+    #[serde(default)]
+    pub added: Vec<String>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+    #[serde(default)]
+    pub modified: Vec<String>,
+}
+
+impl PushEvent {
+    /// Whether any of the pushed commits added, removed or modified the file at `path`.
+    pub fn touches(&self, path: &str) -> bool {
+        self.commits.iter().any(|c| {
+            c.added
+                .iter()
+                .chain(&c.removed)
+                .chain(&c.modified)
+                .any(|p| p == path)
+        })
+    }
 }
 
 #[derive(Debug)]
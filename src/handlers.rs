@@ -1,5 +1,8 @@
 use crate::config::{self, Config, ConfigurationError};
-use crate::github::{Event, GithubClient, IssueCommentAction, IssuesAction, IssuesEvent};
+use crate::github::{
+    Event, GithubClient, IssueCommentAction, IssuesAction, IssuesEvent, UnknownLabels,
+};
+use crate::zulip::ZulipClient;
 use octocrab::Octocrab;
 use parser::command::{Command, Input};
 use std::fmt;
@@ -14,6 +17,16 @@ pub enum HandlerError {
 
 impl std::error::Error for HandlerError {}
 
+impl From<anyhow::Error> for HandlerError {
+    fn from(err: anyhow::Error) -> HandlerError {
+        // Some errors are caused by the user's input, and should be reported back to them.
+        match err.downcast::<UnknownLabels>() {
+            Ok(err) => HandlerError::Message(err.to_string()),
+            Err(err) => HandlerError::Other(err),
+        }
+    }
+}
+
 impl fmt::Display for HandlerError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -25,8 +38,12 @@ impl fmt::Display for HandlerError {
 
 mod assign;
 mod autolabel;
+mod backport;
+mod exclusive_labels;
+mod fcp;
 mod github_releases;
 mod glacier;
+mod labels;
 mod major_change;
 mod milestone_prs;
 mod nominate;
@@ -65,6 +82,38 @@ pub async fn handle(ctx: &Context, event: &Event) -> Vec<HandlerError> {
         );
     }
 
+    if let Err(e) = labels::handle(ctx, event).await {
+        log::error!(
+            "failed to process event {:?} with labels handler: {:?}",
+            event,
+            e
+        );
+    }
+
+    if let Err(e) = fcp::handle(ctx, event).await {
+        log::error!(
+            "failed to process event {:?} with fcp handler: {:?}",
+            event,
+            e
+        );
+    }
+
+    if let Err(e) = backport::handle(ctx, event).await {
+        log::error!(
+            "failed to process event {:?} with backport handler: {:?}",
+            event,
+            e
+        );
+    }
+
+    if let Err(e) = glacier::handle(ctx, event).await {
+        log::error!(
+            "failed to process event {:?} with glacier handler: {:?}",
+            event,
+            e
+        );
+    }
+
     if let Err(e) = milestone_prs::handle(ctx, event).await {
         log::error!(
             "failed to process event {:?} with milestone_prs handler: {:?}",
@@ -90,6 +139,16 @@ pub async fn handle(ctx: &Context, event: &Event) -> Vec<HandlerError> {
     errors
 }
 
+/// Runs the handlers which act on a schedule rather than on events.
+pub async fn handle_scheduled(ctx: &Context) {
+    if let Err(e) = major_change::accept_proposals(ctx).await {
+        log::error!("failed to accept major change proposals: {:?}", e);
+    }
+    if let Err(e) = fcp::finish_fcps(ctx).await {
+        log::error!("failed to finish final comment periods: {:?}", e);
+    }
+}
+
 macro_rules! issue_handlers {
     ($($name:ident,)*) => {
         async fn handle_issue(
@@ -103,7 +162,7 @@ macro_rules! issue_handlers {
                 Err(err) => errors.push(HandlerError::Message(err)),
                 Ok(Some(input)) => {
                     if let Some(config) = &config.$name {
-                        $name::handle_input(ctx, config, event, input).await.unwrap_or_else(|err| errors.push(HandlerError::Other(err)));
+                        $name::handle_input(ctx, config, event, input).await.unwrap_or_else(|err| errors.push(err.into()));
                     } else {
                         errors.push(HandlerError::Message(format!(
                             "The feature `{}` is not enabled in this repository.\n\
@@ -125,12 +184,16 @@ macro_rules! issue_handlers {
 // Each module in the list must contain the functions `parse_input` and `handle_input`.
 issue_handlers! {
     autolabel,
+    exclusive_labels,
     major_change,
     notify_zulip,
+    prioritize,
 }
 
 macro_rules! command_handlers {
-    ($($name:ident: $enum:ident,)*) => {
+    (@handler $name:ident) => { $name::handle_command };
+    (@handler $name:ident $handler:ident) => { $name::$handler };
+    ($($name:ident: $enum:ident $(=> $handler:ident)?,)*) => {
         async fn handle_command(
             ctx: &Context,
             event: &Event,
@@ -155,6 +218,12 @@ macro_rules! command_handlers {
                 }
             }
 
+            // The bot's own comments quote text from elsewhere, e.g. Zulip summaries.
+            if event.user().login == ctx.username {
+                log::debug!("skipping event, it was made by {}", ctx.username);
+                return;
+            }
+
             let input = Input::new(&body, &ctx.username);
             let commands = if let Some(previous) = event.comment_from() {
                 let prev_commands = Input::new(&previous, &ctx.username).collect::<Vec<_>>();
@@ -175,6 +244,9 @@ macro_rules! command_handlers {
                 Err(e @ ConfigurationError::Toml(_)) => {
                     return errors.push(HandlerError::Message(e.to_string()));
                 }
+                Err(e @ ConfigurationError::Invalid(_)) => {
+                    return errors.push(HandlerError::Message(e.to_string()));
+                }
                 Err(e @ ConfigurationError::Http(_)) => {
                     return errors.push(HandlerError::Other(e.clone().into()));
                 }
@@ -185,9 +257,10 @@ macro_rules! command_handlers {
                     $(
                     Command::$enum(Ok(command)) => {
                         if let Some(config) = &config.$name {
-                            $name::handle_command(ctx, config, event, command)
+                            let handler = command_handlers!(@handler $name $($handler)?);
+                            handler(ctx, config, event, command)
                                 .await
-                                .unwrap_or_else(|err| errors.push(HandlerError::Other(err)));
+                                .unwrap_or_else(|err| errors.push(err.into()));
                         } else {
                             errors.push(HandlerError::Message(format!(
                                 "The feature `{}` is not enabled in this repository.\n\
@@ -216,14 +289,18 @@ macro_rules! command_handlers {
 // This is for handlers for commands parsed by the `parser` crate.
 // Each variant of `parser::command::Command` must be in this list,
 // preceded by the module containing the coresponding `handle_command` function
+// (or the function given after `=>`)
 command_handlers! {
     assign: Assign,
+    fcp: Fcp,
     glacier: Glacier,
     nominate: Nominate,
     ping: Ping,
     prioritize: Prioritize,
     relabel: Relabel,
     major_change: Second,
+    major_change: Concern => handle_concern,
+    major_change: Withdraw => handle_withdraw,
 }
 
 pub struct Context {
@@ -231,4 +308,5 @@ pub struct Context {
     pub db: DbClient,
     pub username: String,
     pub octocrab: Octocrab,
+    pub zulip: ZulipClient,
 }
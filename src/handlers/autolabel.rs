@@ -1,10 +1,19 @@
 use crate::{
-    config::AutolabelConfig,
-    github::{IssuesAction, IssuesEvent, Label},
+    config::{AutolabelConfig, AutolabelLabelConfig},
+    db::label_changes::{get_label_changes, LabelChange},
+    github::{IssuesAction, IssuesEvent, Label, LabelChangeOrigin, PullRequestFile},
     handlers::Context,
 };
+use anyhow::Context as _;
+use parser::code_block::ColorCodeBlocks;
+use regex::Regex;
+
 pub(super) struct AutolabelInput {
     labels: Vec<Label>,
+    /// Whether to also add the labels triggered by the files changed in the pull request.
+    check_files: bool,
+    /// The state labels replaced by the new state of the pull request.
+    remove: Vec<Label>,
 }
 
 pub(super) fn parse_input(
@@ -17,26 +26,9 @@ pub(super) fn parse_input(
             let mut autolabels = Vec::new();
             let applied_label = &event.label.as_ref().expect("label").name;
 
-            'outer: for (label, config) in config.get_by_trigger(applied_label) {
-                let exclude_patterns: Vec<glob::Pattern> = config
-                    .exclude_labels
-                    .iter()
-                    .filter_map(|label| match glob::Pattern::new(label) {
-                        Ok(exclude_glob) => Some(exclude_glob),
-                        Err(error) => {
-                            log::error!("Invalid glob pattern: {}", error);
-                            None
-                        }
-                    })
-                    .collect();
-
-                for label in event.issue.labels() {
-                    for pat in &exclude_patterns {
-                        if pat.matches(&label.name) {
-                            // If we hit an excluded label, ignore this autolabel and check the next
-                            continue 'outer;
-                        }
-                    }
+            for (label, config) in config.get_by_trigger(applied_label) {
+                if is_excluded(config, event) {
+                    continue;
                 }
 
                 // If we reach here, no excluded labels were found, so we should apply the autolabel.
@@ -45,7 +37,50 @@ pub(super) fn parse_input(
                 });
             }
             if !autolabels.is_empty() {
-                return Ok(Some(AutolabelInput { labels: autolabels }));
+                return Ok(Some(AutolabelInput {
+                    labels: autolabels,
+                    check_files: false,
+                    remove: Vec::new(),
+                }));
+            }
+        }
+    }
+    if matches!(
+        event.action,
+        IssuesAction::Opened | IssuesAction::Synchronize | IssuesAction::ReviewSubmitted
+    ) {
+        if let Some(config) = config {
+            let mut autolabels = Vec::new();
+            for (label, config) in &config.labels {
+                if !is_excluded(config, event) && event_triggers(config, event) {
+                    autolabels.push(Label {
+                        name: label.to_owned(),
+                    });
+                }
+            }
+            let check_files = event.action != IssuesAction::ReviewSubmitted
+                && event.issue.is_pr()
+                && config.labels.values().any(|l| !l.trigger_files.is_empty());
+            let remove = if autolabels.iter().any(|l| config.labels[&l.name].is_state()) {
+                config
+                    .labels
+                    .iter()
+                    .filter(|(label, config)| {
+                        config.is_state() && !autolabels.iter().any(|l| l.name == **label)
+                    })
+                    .map(|(label, _)| Label {
+                        name: label.to_owned(),
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            if !autolabels.is_empty() || check_files {
+                return Ok(Some(AutolabelInput {
+                    labels: autolabels,
+                    check_files,
+                    remove,
+                }));
             }
         }
     }
@@ -56,25 +91,402 @@ pub(super) fn parse_input(
             labels_excluded.remove(x);
             return Ok(Some(AutolabelInput {
                 labels: labels_excluded,
+                check_files: false,
+                remove: Vec::new(),
             }));
         }
     }
     Ok(None)
 }
 
+/// Whether the issue has one of the labels excluding the autolabel.
+fn is_excluded(config: &AutolabelLabelConfig, event: &IssuesEvent) -> bool {
+    let exclude_patterns: Vec<glob::Pattern> = config
+        .exclude_labels
+        .iter()
+        .filter_map(|label| match glob::Pattern::new(label) {
+            Ok(exclude_glob) => Some(exclude_glob),
+            Err(error) => {
+                log::error!("Invalid glob pattern: {}", error);
+                None
+            }
+        })
+        .collect();
+
+    event
+        .issue
+        .labels()
+        .iter()
+        .any(|label| exclude_patterns.iter().any(|pat| pat.matches(&label.name)))
+}
+
+/// Whether the event triggers the autolabel, other than through labels and files.
+fn event_triggers(config: &AutolabelLabelConfig, event: &IssuesEvent) -> bool {
+    let is_author = event.sender.login == event.issue.user.login;
+    match event.action {
+        IssuesAction::Opened => {
+            body_triggers(config, &event.issue.title, &event.issue.body)
+                || event
+                    .issue
+                    .author_association
+                    .as_ref()
+                    .map_or(false, |a| config.trigger_author_associations.contains(a))
+        }
+        IssuesAction::Synchronize => config.trigger_author_push && is_author,
+        IssuesAction::ReviewSubmitted => {
+            !is_author
+                && event.review_state.as_ref().map_or(false, |state| {
+                    config
+                        .trigger_reviews
+                        .iter()
+                        .any(|s| s.eq_ignore_ascii_case(state))
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Whether the title or the description of an issue trigger the autolabel.
+fn body_triggers(config: &AutolabelLabelConfig, title: &str, body: &str) -> bool {
+    if config.trigger_body.is_empty() && config.trigger_sections.is_empty() {
+        return false;
+    }
+    let code = ColorCodeBlocks::new(body);
+    let outside_code = |range: std::ops::Range<usize>| {
+        config.match_code_blocks || code.overlaps_code(range).is_none()
+    };
+
+    // Checked by `Config::validate`.
+    let regexes = config
+        .trigger_body
+        .iter()
+        .filter_map(|re| Regex::new(re).ok())
+        .collect::<Vec<_>>();
+    for re in &regexes {
+        if re.is_match(title) || re.find_iter(body).any(|m| outside_code(m.range())) {
+            return true;
+        }
+    }
+
+    let mut start = 0;
+    for line in body.split('\n') {
+        let range = start..start + line.len();
+        start = range.end + 1;
+        let heading = line.trim();
+        if !heading.starts_with('#') || !outside_code(range) {
+            continue;
+        }
+        let heading = heading.trim_start_matches('#').trim();
+        if config
+            .trigger_sections
+            .iter()
+            .any(|section| section.eq_ignore_ascii_case(heading))
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// Returns the labels triggered by the files changed in the pull request, leaving out the ones
+/// which were removed by hand before.
+async fn file_labels(
+    ctx: &Context,
+    config: &AutolabelConfig,
+    event: &IssuesEvent,
+) -> anyhow::Result<Vec<Label>> {
+    let files = event.issue.files(&ctx.github).await?;
+    let mut labels = Vec::new();
+    for (label, config) in &config.labels {
+        if config.trigger_files.is_empty() || is_excluded(config, event) {
+            continue;
+        }
+        if files_trigger(config, &files) {
+            labels.push(Label {
+                name: label.to_owned(),
+            });
+        }
+    }
+    if labels.is_empty() {
+        return Ok(labels);
+    }
+
+    let changes =
+        get_label_changes(&ctx.db, &event.repository.full_name, event.issue.number).await?;
+    let removed_by_hand = removed_by_hand(&changes);
+    labels.retain(|label| !removed_by_hand.contains(&label.name.as_str()));
+    Ok(labels)
+}
+
+/// Whether any of `files` matches the `trigger_files` globs, before or after being renamed.
+fn files_trigger(config: &AutolabelLabelConfig, files: &[PullRequestFile]) -> bool {
+    let patterns: Vec<glob::Pattern> = config
+        .trigger_files
+        .iter()
+        .filter_map(|file| match glob::Pattern::new(file) {
+            Ok(file_glob) => Some(file_glob),
+            Err(error) => {
+                log::error!("Invalid glob pattern: {}", error);
+                None
+            }
+        })
+        .collect();
+    files.iter().any(|file| {
+        std::iter::once(&file.filename)
+            .chain(&file.previous_filename)
+            .any(|name| patterns.iter().any(|pat| pat.matches(name)))
+    })
+}
+
+/// Returns the labels removed outside of triagebot, which are recorded without a handler.
+fn removed_by_hand(changes: &[LabelChange]) -> Vec<&str> {
+    changes
+        .iter()
+        .filter(|change| change.handler.is_none())
+        .flat_map(|change| change.removed())
+        .collect()
+}
+
 pub(super) async fn handle_input(
     ctx: &Context,
-    _config: &AutolabelConfig,
+    config: &AutolabelConfig,
     event: &IssuesEvent,
     input: AutolabelInput,
 ) -> anyhow::Result<()> {
-    let mut labels = event.issue.labels().to_owned();
-    for label in input.labels {
+    let mut new_labels = input.labels;
+    if input.check_files {
+        new_labels.extend(file_labels(ctx, config, event).await?);
+    }
+    let previous = event.issue.labels();
+    let mut labels = previous.to_owned();
+    labels.retain(|label| !input.remove.contains(label));
+    for label in new_labels {
         // Don't add the label if it's already there
         if !labels.contains(&label) {
             labels.push(label);
         }
     }
-    event.issue.set_labels(&ctx.github, labels).await?;
+    if input.check_files && labels == previous {
+        return Ok(());
+    }
+    let added = labels
+        .iter()
+        .filter(|l| !previous.contains(l))
+        .map(|l| l.name.clone())
+        .collect::<Vec<_>>();
+    event
+        .issue
+        .set_labels(
+            &ctx.github,
+            &ctx.db,
+            LabelChangeOrigin::from_issues_event("autolabel", event),
+            labels,
+        )
+        .await?;
+
+    for label in added {
+        let template = match config.labels.get(&label).and_then(|c| c.comment.as_ref()) {
+            Some(template) => template,
+            None => continue,
+        };
+        let mut context = tera::Context::new();
+        context.insert("author", &event.issue.user.login);
+        context.insert("number", &event.issue.number);
+        context.insert("title", &event.issue.title);
+        context.insert("repo", &event.repository.full_name);
+        context.insert("label", &label);
+        let comment = tera::Tera::one_off(template, &context, false)
+            .with_context(|| format!("rendering the comment of autolabel {}", label))?;
+        event.issue.post_comment(&ctx.github, &comment).await?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(trigger_body: &[&str], trigger_sections: &[&str]) -> AutolabelLabelConfig {
+        AutolabelLabelConfig {
+            trigger_labels: Vec::new(),
+            exclude_labels: Vec::new(),
+            trigger_files: Vec::new(),
+            trigger_body: trigger_body.iter().map(|s| s.to_string()).collect(),
+            trigger_sections: trigger_sections.iter().map(|s| s.to_string()).collect(),
+            match_code_blocks: false,
+            trigger_author_associations: Vec::new(),
+            trigger_reviews: Vec::new(),
+            trigger_author_push: false,
+            comment: None,
+        }
+    }
+
+    fn event(action: &str, sender: &str, association: &str) -> IssuesEvent {
+        serde_json::from_value(serde_json::json!({
+            "action": action,
+            "issue": {
+                "number": 1,
+                "body": "",
+                "created_at": "2020-01-01T00:00:00Z",
+                "title": "Title",
+                "html_url": "https://github.com/rust-lang/rust/pull/1",
+                "user": { "login": "author", "id": 1 },
+                "labels": [],
+                "assignees": [],
+                "author_association": association,
+                "pull_request": {},
+                "comments_url": "https://api.github.com/repos/rust-lang/rust/issues/1/comments",
+            },
+            "changes": null,
+            "repository": { "full_name": "rust-lang/rust" },
+            "label": null,
+            "sender": { "login": sender, "id": 2 },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn author_associations() {
+        let config = AutolabelLabelConfig {
+            trigger_author_associations: vec!["FIRST_TIME_CONTRIBUTOR".to_owned()],
+            ..config(&[], &[])
+        };
+        assert!(event_triggers(
+            &config,
+            &event("opened", "author", "FIRST_TIME_CONTRIBUTOR")
+        ));
+        assert!(!event_triggers(
+            &config,
+            &event("opened", "author", "MEMBER")
+        ));
+        // Only new pull requests are labeled.
+        assert!(!event_triggers(
+            &config,
+            &event("synchronize", "author", "FIRST_TIME_CONTRIBUTOR")
+        ));
+    }
+
+    #[test]
+    fn reviews() {
+        let config = AutolabelLabelConfig {
+            trigger_reviews: vec!["changes_requested".to_owned()],
+            ..config(&[], &[])
+        };
+        let mut review = event("synchronize", "reviewer", "MEMBER");
+        review.action = IssuesAction::ReviewSubmitted;
+        review.review_state = Some("CHANGES_REQUESTED".to_owned());
+        assert!(event_triggers(&config, &review));
+        review.review_state = Some("approved".to_owned());
+        assert!(!event_triggers(&config, &review));
+        // Authors reviewing their own pull request don't change its state.
+        review.review_state = Some("changes_requested".to_owned());
+        review.sender.login = "author".to_owned();
+        assert!(!event_triggers(&config, &review));
+    }
+
+    #[test]
+    fn author_push() {
+        let config = AutolabelLabelConfig {
+            trigger_author_push: true,
+            ..config(&[], &[])
+        };
+        assert!(event_triggers(
+            &config,
+            &event("synchronize", "author", "MEMBER")
+        ));
+        assert!(!event_triggers(
+            &config,
+            &event("synchronize", "bors", "MEMBER")
+        ));
+        assert!(!event_triggers(
+            &config,
+            &event("opened", "author", "MEMBER")
+        ));
+    }
+
+    #[test]
+    fn body_regex() {
+        let config = config(&["error: internal compiler error"], &[]);
+        assert!(body_triggers(
+            &config,
+            "ICE",
+            "It says error: internal compiler error"
+        ));
+        assert!(!body_triggers(
+            &config,
+            "ICE",
+            "```\nerror: internal compiler error\n```"
+        ));
+        let config = AutolabelLabelConfig {
+            match_code_blocks: true,
+            ..config
+        };
+        assert!(body_triggers(
+            &config,
+            "ICE",
+            "```\nerror: internal compiler error\n```"
+        ));
+    }
+
+    #[test]
+    fn body_sections() {
+        let config = config(&[], &["Platform: Windows"]);
+        assert!(body_triggers(&config, "", "Hi\n\n### platform: windows\n"));
+        assert!(!body_triggers(&config, "", "Platform: Windows"));
+        assert!(!body_triggers(
+            &config,
+            "",
+            "```\n### Platform: Windows\n```"
+        ));
+    }
+
+    fn file(filename: &str, previous_filename: Option<&str>) -> PullRequestFile {
+        PullRequestFile {
+            filename: filename.to_owned(),
+            previous_filename: previous_filename.map(|f| f.to_owned()),
+        }
+    }
+
+    #[test]
+    fn trigger_files() {
+        let config = AutolabelLabelConfig {
+            trigger_files: vec!["src/librustdoc/**".to_owned(), "*.md".to_owned()],
+            ..config(&[], &[])
+        };
+        assert!(files_trigger(
+            &config,
+            &[file("src/librustdoc/html/render.rs", None)]
+        ));
+        assert!(files_trigger(&config, &[file("README.md", None)]));
+        // Files moved out of the matched paths still trigger.
+        assert!(files_trigger(
+            &config,
+            &[file("src/rustdoc/lib.rs", Some("src/librustdoc/lib.rs"))]
+        ));
+        assert!(!files_trigger(
+            &config,
+            &[file("src/librustc/lib.rs", None)]
+        ));
+        assert!(!files_trigger(&config, &[]));
+    }
+
+    #[test]
+    fn labels_removed_by_hand() {
+        let change = |handler: Option<&str>, before: &[&str], after: &[&str]| LabelChange {
+            repo: "rust-lang/rust".to_owned(),
+            issue_number: 1,
+            actor: None,
+            comment_url: None,
+            handler: handler.map(|h| h.to_owned()),
+            labels_before: before.iter().map(|l| l.to_string()).collect(),
+            labels_after: after.iter().map(|l| l.to_string()).collect(),
+            time: chrono::DateTime::parse_from_rfc3339("2020-01-01T00:00:00Z").unwrap(),
+        };
+        let changes = [
+            change(None, &["T-rustdoc", "A-docs"], &["A-docs"]),
+            change(Some("relabel"), &["A-docs", "T-lang"], &["T-lang"]),
+            change(None, &[], &["T-compiler"]),
+        ];
+        assert_eq!(removed_by_hand(&changes), vec!["T-rustdoc"]);
+    }
+}
@@ -0,0 +1,361 @@
+//! Purpose: Carry accepted backports through to a merged backport PR.
+//!
+//! When a pull request is beta-accepted (see the `nominate` handler) and has been merged, its merge
+//! commit is cherry-picked onto the beta branch and a backport PR is opened; PRs accepted before
+//! they are merged are backported once they merge. When the backport PR is merged, the original
+//! PR loses its `beta-nominated` label and is milestoned with the beta version. Stable backports
+//! are only tracked, as the release team prepares them by hand.
+//!
+//! This is only enabled for repositories with a `[backport]` section in `triagebot.toml`. The
+//! state of every backport is kept in the database, and listed on the `/backports` page.
+
+use crate::{
+    config::BackportConfig,
+    db::backports::{self, Backport},
+    db::nominations,
+    github::{Event, IssuesAction, LabelChangeOrigin, Repository},
+    handlers::{milestone_prs, Context},
+};
+use anyhow::Context as _;
+use chrono::Utc;
+
+/// Records that the PR of `event` was accepted for backporting to `channel`, and opens the beta
+/// backport PR if it is already merged.
+pub(super) async fn accept(ctx: &Context, event: &Event, channel: &str) -> anyhow::Result<()> {
+    let issue = event.issue().unwrap();
+    let repo = issue.repository().to_string();
+    nominations::resolve_nominations(
+        &ctx.db,
+        &repo,
+        issue.number,
+        Some(channel),
+        None,
+        &event.user().login,
+        "accepted",
+    )
+    .await?;
+    let mut backport = Backport {
+        repo,
+        pr_number: issue.number,
+        channel: channel.to_owned(),
+        state: backports::ACCEPTED.to_owned(),
+        backport_pr: None,
+        error: None,
+        updated_at: Utc::now().into(),
+    };
+    backports::upsert_backport(&ctx.db, &backport).await?;
+
+    if channel != nominations::BETA {
+        return Ok(());
+    }
+    let config = match crate::config::get(&ctx.github, event.repo_name()).await {
+        Ok(config) => config,
+        Err(_) => return Ok(()),
+    };
+    if let Some(config) = &config.backport {
+        open_backport(ctx, config, &mut backport).await?;
+    }
+    Ok(())
+}
+
+/// Records a new nomination for backporting to `channel`.
+pub(super) async fn nominate(ctx: &Context, event: &Event, channel: &str) -> anyhow::Result<()> {
+    let issue = event.issue().unwrap();
+    let repo = issue.repository().to_string();
+    // Nominating again should not undo the progress of an accepted backport.
+    if backports::get_backport(&ctx.db, &repo, issue.number, channel)
+        .await?
+        .is_some()
+    {
+        return Ok(());
+    }
+    let backport = Backport {
+        repo,
+        pr_number: issue.number,
+        channel: channel.to_owned(),
+        state: backports::NOMINATED.to_owned(),
+        backport_pr: None,
+        error: None,
+        updated_at: Utc::now().into(),
+    };
+    backports::upsert_backport(&ctx.db, &backport).await
+}
+
+/// Records that the nomination of `pr_number` for backporting to `channel` was resolved without
+/// accepting it.
+pub(super) async fn decline(
+    ctx: &Context,
+    repo: &str,
+    pr_number: u64,
+    channel: &str,
+) -> anyhow::Result<()> {
+    match backports::get_backport(&ctx.db, repo, pr_number, channel).await? {
+        Some(mut backport) if backport.state == backports::NOMINATED => {
+            backport.state = backports::DECLINED.to_owned();
+            backport.updated_at = Utc::now().into();
+            backports::upsert_backport(&ctx.db, &backport).await
+        }
+        _ => Ok(()),
+    }
+}
+
+pub async fn handle(ctx: &Context, event: &Event) -> anyhow::Result<()> {
+    let e = match event {
+        Event::Issue(e) if e.action == IssuesAction::Closed && e.issue.merged => e,
+        _ => return Ok(()),
+    };
+    let config = match crate::config::get(&ctx.github, event.repo_name()).await {
+        Ok(config) => config,
+        Err(_) => return Ok(()),
+    };
+    let config = match &config.backport {
+        Some(config) => config,
+        None => return Ok(()),
+    };
+    let repo = e.issue.repository().to_string();
+
+    if let Some(backport) = backports::get_backport_by_pr(&ctx.db, &repo, e.issue.number).await? {
+        let origin = LabelChangeOrigin::from_issues_event("backport", e);
+        return finish_backport(ctx, config, backport, origin).await;
+    }
+
+    match backports::get_backport(&ctx.db, &repo, e.issue.number, nominations::BETA).await? {
+        Some(mut backport) if backport.state == backports::ACCEPTED => {
+            open_backport(ctx, config, &mut backport).await
+        }
+        _ => Ok(()),
+    }
+}
+
+fn channel_branch<'a>(config: &'a BackportConfig, channel: &'a str) -> &'a str {
+    if channel == nominations::BETA {
+        &config.beta_branch
+    } else {
+        channel
+    }
+}
+
+/// Opens the backport PR of a merged PR, recording the failure if it can't be.
+async fn open_backport(
+    ctx: &Context,
+    config: &BackportConfig,
+    backport: &mut Backport,
+) -> anyhow::Result<()> {
+    #[derive(serde::Deserialize)]
+    struct PullRequest {
+        title: String,
+        merged: bool,
+        merge_commit_sha: Option<String>,
+    }
+
+    let pr: PullRequest = ctx
+        .octocrab
+        .get::<PullRequest, _, ()>(
+            format!("repos/{}/pulls/{}", backport.repo, backport.pr_number),
+            None,
+        )
+        .await?;
+    let merge_commit = match (pr.merged, pr.merge_commit_sha) {
+        (true, Some(sha)) => sha,
+        // Backported once it is merged.
+        _ => return Ok(()),
+    };
+
+    let base = channel_branch(config, &backport.channel);
+    let branch = format!(
+        "triagebot-{}-backport-{}",
+        backport.channel, backport.pr_number
+    );
+    let issue = Repository {
+        full_name: backport.repo.clone(),
+    }
+    .get_issue(&ctx.github, backport.pr_number)
+    .await?;
+
+    match cherry_pick(ctx, &backport.repo, &merge_commit, base, &branch).await {
+        Ok(()) => {
+            let (owner, name) = backport.repo.split_at(backport.repo.find('/').unwrap());
+            let backport_pr = ctx
+                .octocrab
+                .pulls(owner, &name[1..])
+                .create(format!("[{}] {}", base, pr.title), branch.as_str(), base)
+                .body(format!(
+                    "Backport of #{} to `{}`.\n\nAutomatically created by triagebot.",
+                    backport.pr_number, base
+                ))
+                .send()
+                .await
+                .context("opening backport PR")?;
+            backport.state = backports::OPENED.to_owned();
+            backport.backport_pr = Some(backport_pr.number);
+            backport.error = None;
+        }
+        Err(e) => {
+            log::warn!(
+                "failed to backport {}#{}: {:?}",
+                backport.repo,
+                backport.pr_number,
+                e
+            );
+            backport.state = backports::FAILED.to_owned();
+            backport.error = Some(e.to_string());
+            issue
+                .post_comment(
+                    &ctx.github,
+                    &format!(
+                        "The backport to `{}` could not be opened automatically ({}); \
+                         it needs to be backported by hand.",
+                        base, e
+                    ),
+                )
+                .await?;
+        }
+    }
+    backport.updated_at = Utc::now().into();
+    backports::upsert_backport(&ctx.db, backport).await
+}
+
+/// Marks a backport as merged, and updates the labels and milestone of the backported PR.
+async fn finish_backport(
+    ctx: &Context,
+    config: &BackportConfig,
+    mut backport: Backport,
+    origin: LabelChangeOrigin<'_>,
+) -> anyhow::Result<()> {
+    backport.state = backports::MERGED.to_owned();
+    backport.updated_at = Utc::now().into();
+    backports::upsert_backport(&ctx.db, &backport).await?;
+
+    let issue = Repository {
+        full_name: backport.repo.clone(),
+    }
+    .get_issue(&ctx.github, backport.pr_number)
+    .await?;
+    let nominated = format!("{}-nominated", backport.channel);
+    if issue.labels().iter().any(|l| l.name == nominated) {
+        let labels = issue
+            .labels()
+            .iter()
+            .filter(|l| l.name != nominated)
+            .cloned()
+            .collect();
+        issue
+            .set_labels(&ctx.github, &ctx.db, origin, labels)
+            .await?;
+    }
+
+    let branch = channel_branch(config, &backport.channel);
+    match milestone_prs::get_version(ctx, branch).await? {
+        Some(version) => issue.set_milestone(&ctx.github, &version).await?,
+        None => log::error!("could not find the version of {}", branch),
+    }
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+struct GitRef {
+    object: GitObject,
+}
+
+#[derive(serde::Deserialize)]
+struct GitObject {
+    sha: String,
+}
+
+#[derive(serde::Deserialize)]
+struct GitCommit {
+    sha: String,
+    message: String,
+    tree: GitObject,
+    parents: Vec<GitObject>,
+}
+
+/// Creates `branch` with `commit` cherry-picked onto `base`, relative to its first parent.
+///
+/// GitHub has no API to cherry-pick, so a commit with the tree of `base` is created on top of the
+/// parent of `commit`. Merging `commit` into it applies exactly the changes of `commit` to the
+/// tree of `base`, and the resulting tree is committed on top of `base`.
+async fn cherry_pick(
+    ctx: &Context,
+    repo: &str,
+    commit: &str,
+    base: &str,
+    branch: &str,
+) -> anyhow::Result<()> {
+    let octocrab = &ctx.octocrab;
+    let base_ref: GitRef = octocrab
+        .get::<GitRef, _, ()>(format!("repos/{}/git/ref/heads/{}", repo, base), None)
+        .await
+        .with_context(|| format!("getting branch {}", base))?;
+    let base_commit: GitCommit = octocrab
+        .get::<GitCommit, _, ()>(
+            format!("repos/{}/git/commits/{}", repo, base_ref.object.sha),
+            None,
+        )
+        .await?;
+    let picked: GitCommit = octocrab
+        .get::<GitCommit, _, ()>(format!("repos/{}/git/commits/{}", repo, commit), None)
+        .await?;
+    let parent = picked
+        .parents
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("cannot cherry-pick root commit {}", commit))?;
+
+    let sibling: GitCommit = octocrab
+        .post(
+            format!("repos/{}/git/commits", repo),
+            Some(&serde_json::json!({
+                "message": format!("Temporary commit for cherry-picking {}", commit),
+                "tree": base_commit.tree.sha,
+                "parents": [parent.sha],
+            })),
+        )
+        .await?;
+    let created: Result<serde_json::Value, _> = octocrab
+        .post(
+            format!("repos/{}/git/refs", repo),
+            Some(&serde_json::json!({
+                "ref": format!("refs/heads/{}", branch),
+                "sha": sibling.sha,
+            })),
+        )
+        .await;
+    if created.is_err() {
+        // Left over from an earlier attempt.
+        let _: serde_json::Value = octocrab
+            .patch(
+                format!("repos/{}/git/refs/heads/{}", repo, branch),
+                Some(&serde_json::json!({ "sha": sibling.sha, "force": true })),
+            )
+            .await
+            .with_context(|| format!("creating branch {}", branch))?;
+    }
+
+    let repository = Repository {
+        full_name: repo.to_string(),
+    };
+    let message = format!("Cherry-pick {}", commit);
+    let tree = repository
+        .merge(&ctx.github, branch, commit, &message)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("{} does not apply cleanly onto {}", commit, base))?;
+
+    let cherry_picked: GitCommit = octocrab
+        .post(
+            format!("repos/{}/git/commits", repo),
+            Some(&serde_json::json!({
+                "message": picked.message,
+                "tree": tree,
+                "parents": [base_commit.sha],
+            })),
+        )
+        .await?;
+    let _: serde_json::Value = octocrab
+        .patch(
+            format!("repos/{}/git/refs/heads/{}", repo, branch),
+            Some(&serde_json::json!({ "sha": cherry_picked.sha, "force": true })),
+        )
+        .await
+        .with_context(|| format!("updating branch {}", branch))?;
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! Purpose: Keep the labels of an exclusive group (e.g. `P-*`) from being applied together.
+//!
+//! Labels set by triagebot itself are resolved in `Issue::set_labels`. This handler covers labels
+//! added through the GitHub UI: the other labels of the new label's groups are removed.
+
+use crate::{
+    config::ExclusiveLabelsConfig,
+    github::{IssuesAction, IssuesEvent, Label, LabelChangeOrigin},
+    handlers::Context,
+};
+
+pub(super) struct ExclusiveLabelsInput {
+    conflicting: Vec<Label>,
+}
+
+pub(super) fn parse_input(
+    _ctx: &Context,
+    event: &IssuesEvent,
+    config: Option<&ExclusiveLabelsConfig>,
+) -> Result<Option<ExclusiveLabelsInput>, String> {
+    if event.action != IssuesAction::Labeled {
+        return Ok(None);
+    }
+    let config = match config {
+        Some(config) => config,
+        None => return Ok(None),
+    };
+    let applied_label = &event.label.as_ref().expect("label").name;
+    let groups = config.groups_of(applied_label);
+    if groups.is_empty() {
+        return Ok(None);
+    }
+
+    let conflicting = event
+        .issue
+        .labels()
+        .iter()
+        .filter(|l| {
+            l.name != *applied_label
+                && config
+                    .groups_of(&l.name)
+                    .iter()
+                    .any(|group| groups.contains(group))
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    if conflicting.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(ExclusiveLabelsInput { conflicting }))
+}
+
+pub(super) async fn handle_input(
+    ctx: &Context,
+    _config: &ExclusiveLabelsConfig,
+    event: &IssuesEvent,
+    input: ExclusiveLabelsInput,
+) -> anyhow::Result<()> {
+    let labels = event
+        .issue
+        .labels()
+        .iter()
+        .filter(|l| !input.conflicting.contains(l))
+        .cloned()
+        .collect();
+    event
+        .issue
+        .set_labels(
+            &ctx.github,
+            &ctx.db,
+            LabelChangeOrigin::from_issues_event("exclusive_labels", event),
+            labels,
+        )
+        .await?;
+    Ok(())
+}
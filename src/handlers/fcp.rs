@@ -0,0 +1,472 @@
+//! Purpose: Run final comment periods (FCPs), in the style of rfcbot.
+//!
+//! `@rustbot fcp merge|close|postpone` proposes an FCP, with the members of the teams of the issue
+//! (from its team labels, see `FcpConfig::teams`) as reviewers. The proposal comment has a
+//! checkbox per reviewer, which is kept up to date as members review, by checking their own box
+//! or with `@rustbot fcp reviewed`. Concerns raised with `@rustbot fcp concern <title>` block the
+//! FCP until they are resolved.
+//!
+//! Once a majority of the reviewers approved, with at most `max-unreviewed` reviews outstanding
+//! and no open concerns, the final comment period starts. When it is over, the issue is labeled
+//! `finished-final-comment-period` along with the `disposition-*` label of the proposal.
+
+use crate::{
+    config::FcpConfig,
+    db::fcps::{self, Concern, Fcp, Review},
+    github::{self, Event, Issue, IssueCommentAction, Label, LabelChangeOrigin, Repository},
+    handlers::Context,
+    interactions::ErrorComment,
+};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use parser::command::fcp::{Disposition, FcpCommand};
+use std::fmt::Write as _;
+
+const PROPOSED_LABEL: &str = "proposed-final-comment-period";
+const FCP_LABEL: &str = "final-comment-period";
+const FINISHED_LABEL: &str = "finished-final-comment-period";
+
+pub(super) async fn handle_command(
+    ctx: &Context,
+    config: &FcpConfig,
+    event: &Event,
+    cmd: FcpCommand,
+) -> anyhow::Result<()> {
+    let issue = event.issue().unwrap();
+    let reviewers = reviewers(ctx, config, issue).await?;
+    if reviewers.is_empty() {
+        let mut labels = config.teams.keys().map(|l| &l[..]).collect::<Vec<_>>();
+        labels.sort();
+        let cmnt = ErrorComment::new(
+            issue,
+            format!(
+                "This issue has no team label, so there is nobody to review an FCP \
+                 (one of `{}` is needed).",
+                labels.join("`, `")
+            ),
+        );
+        cmnt.post(&ctx.github).await?;
+        return Ok(());
+    }
+    let user = &event.user().login;
+    if !reviewers.contains(user) {
+        let cmnt = ErrorComment::new(
+            issue,
+            "Only members of the teams of this issue can take part in its FCP.",
+        );
+        cmnt.post(&ctx.github).await?;
+        return Ok(());
+    }
+
+    let repo = issue.repository().to_string();
+    match (fcps::active_fcp(&ctx.db, &repo, issue.number).await?, cmd) {
+        (None, FcpCommand::Propose(disposition)) => {
+            return propose(ctx, config, event, disposition, &reviewers).await;
+        }
+        (Some(_), FcpCommand::Propose(_)) => {
+            let cmnt = ErrorComment::new(issue, "There already is an FCP for this issue.");
+            cmnt.post(&ctx.github).await?;
+            return Ok(());
+        }
+        (None, _) => {
+            let cmnt = ErrorComment::new(issue, "There is no FCP for this issue.");
+            cmnt.post(&ctx.github).await?;
+            return Ok(());
+        }
+        (Some(fcp), FcpCommand::Cancel) => {
+            return cancel(ctx, event, fcp).await;
+        }
+        (Some(fcp), FcpCommand::Reviewed) => {
+            if !fcps::mark_reviewed(&ctx.db, fcp.fcp_id, user).await? {
+                return Ok(());
+            }
+        }
+        (Some(fcp), FcpCommand::Concern(title)) => {
+            if fcp.state != fcps::PROPOSED {
+                let cmnt = ErrorComment::new(
+                    issue,
+                    "Concerns can only be raised before the final comment period starts.",
+                );
+                cmnt.post(&ctx.github).await?;
+                return Ok(());
+            }
+            if !fcps::raise_concern(&ctx.db, fcp.fcp_id, &title, user).await? {
+                return Ok(());
+            }
+        }
+        (Some(fcp), FcpCommand::Resolve(title)) => {
+            if !fcps::resolve_concern(&ctx.db, fcp.fcp_id, &title, user).await? {
+                let cmnt = ErrorComment::new(
+                    issue,
+                    format!("There is no open concern named `{}`.", title),
+                );
+                cmnt.post(&ctx.github).await?;
+                return Ok(());
+            }
+        }
+    }
+    update(
+        ctx,
+        config,
+        issue,
+        LabelChangeOrigin::from_event("fcp", event),
+    )
+    .await
+}
+
+/// Marks the review of the user who checked their box in the proposal comment as done.
+pub async fn handle(ctx: &Context, event: &Event) -> anyhow::Result<()> {
+    let e = match event {
+        Event::IssueComment(e)
+            if e.action == IssueCommentAction::Edited
+                && e.comment.user.login == ctx.username
+                && e.sender.login != ctx.username =>
+        {
+            e
+        }
+        _ => return Ok(()),
+    };
+    let repo = e.issue.repository().to_string();
+    let fcp = match fcps::active_fcp(&ctx.db, &repo, e.issue.number).await? {
+        Some(fcp) if fcp.comment_id == e.comment.id => fcp,
+        _ => return Ok(()),
+    };
+    let config = match crate::config::get(&ctx.github, &repo).await {
+        Ok(config) => config,
+        Err(_) => return Ok(()),
+    };
+    let config = match &config.fcp {
+        Some(config) => config,
+        None => return Ok(()),
+    };
+
+    // Anyone with write access can edit the comment, so only the box of the editor counts. Boxes
+    // can't be unchecked: reviews are final.
+    if let Some(reviewer) = checked_reviewer(&e.comment.body, &e.sender.login) {
+        fcps::mark_reviewed(&ctx.db, fcp.fcp_id, reviewer).await?;
+    }
+    // Rendering the comment again also unchecks the boxes ticked for others.
+    let origin = LabelChangeOrigin {
+        handler: "fcp",
+        actor: Some(&e.sender.login),
+        comment_url: Some(&e.comment.html_url),
+    };
+    update(ctx, config, &e.issue, origin).await
+}
+
+/// Returns the name of `user` as written on their checked line of the proposal comment, if any.
+fn checked_reviewer<'a>(body: &'a str, user: &str) -> Option<&'a str> {
+    body.lines()
+        .filter_map(|line| line.trim().strip_prefix("- [x] @"))
+        .map(str::trim)
+        .find(|reviewer| reviewer.eq_ignore_ascii_case(user))
+}
+
+/// Returns the members of the teams of the issue, sorted by name.
+async fn reviewers(
+    ctx: &Context,
+    config: &FcpConfig,
+    issue: &Issue,
+) -> anyhow::Result<Vec<String>> {
+    let mut reviewers = Vec::new();
+    for label in issue.labels() {
+        let team = match config.teams.get(&label.name) {
+            Some(team) => team,
+            None => continue,
+        };
+        match github::get_team(&ctx.github, team).await? {
+            Some(team) => reviewers.extend(team.members.into_iter().map(|m| m.github)),
+            None => log::warn!("team {} of label {} does not exist", team, label.name),
+        }
+    }
+    reviewers.sort_by_key(|r| r.to_lowercase());
+    reviewers.dedup();
+    Ok(reviewers)
+}
+
+async fn propose(
+    ctx: &Context,
+    config: &FcpConfig,
+    event: &Event,
+    disposition: Disposition,
+    reviewers: &[String],
+) -> anyhow::Result<()> {
+    let issue = event.issue().unwrap();
+    let proposer = &event.user().login;
+    let reviews = reviewers
+        .iter()
+        .map(|reviewer| Review {
+            reviewer: reviewer.clone(),
+            reviewed: reviewer == proposer,
+        })
+        .collect::<Vec<_>>();
+    let disposition = disposition.to_string();
+    let body = render(ctx, config, proposer, &disposition, &reviews, &[]);
+    let comment = issue.create_comment(&ctx.github, &body).await?;
+
+    let fcp_id = fcps::create_fcp(
+        &ctx.db,
+        &issue.repository().to_string(),
+        issue.number,
+        &disposition,
+        proposer,
+        comment.id,
+        reviewers,
+    )
+    .await?;
+    fcps::mark_reviewed(&ctx.db, fcp_id, proposer).await?;
+
+    let origin = LabelChangeOrigin::from_event("fcp", event);
+    let mut labels = issue.labels().to_owned();
+    labels.push(Label {
+        name: PROPOSED_LABEL.to_owned(),
+    });
+    issue
+        .set_labels(&ctx.github, &ctx.db, origin, labels)
+        .await?;
+
+    // Small teams may be done reviewing already.
+    let fcp = fcps::active_fcp(&ctx.db, &issue.repository().to_string(), issue.number)
+        .await?
+        .expect("just created");
+    maybe_start(ctx, config, issue, &fcp, origin).await
+}
+
+async fn cancel(ctx: &Context, event: &Event, fcp: Fcp) -> anyhow::Result<()> {
+    let issue = event.issue().unwrap();
+    fcps::set_state(&ctx.db, fcp.fcp_id, fcps::CANCELLED).await?;
+    let labels = issue
+        .labels()
+        .iter()
+        .filter(|l| l.name != PROPOSED_LABEL && l.name != FCP_LABEL)
+        .cloned()
+        .collect();
+    issue
+        .set_labels(
+            &ctx.github,
+            &ctx.db,
+            LabelChangeOrigin::from_event("fcp", event),
+            labels,
+        )
+        .await?;
+    issue
+        .post_comment(
+            &ctx.github,
+            &format!(
+                "@{} proposal cancelled by @{}.",
+                fcp.proposer,
+                event.user().login
+            ),
+        )
+        .await
+}
+
+/// Rewrites the proposal comment, and starts the final comment period if it is now due.
+async fn update(
+    ctx: &Context,
+    config: &FcpConfig,
+    issue: &Issue,
+    origin: LabelChangeOrigin<'_>,
+) -> anyhow::Result<()> {
+    // Loaded again, as the callers have just changed it.
+    let fcp = match fcps::active_fcp(&ctx.db, &issue.repository().to_string(), issue.number).await?
+    {
+        Some(fcp) => fcp,
+        None => return Ok(()),
+    };
+    let reviews = fcps::reviews(&ctx.db, fcp.fcp_id).await?;
+    let concerns = fcps::concerns(&ctx.db, fcp.fcp_id).await?;
+    let body = render(
+        ctx,
+        config,
+        &fcp.proposer,
+        &fcp.disposition,
+        &reviews,
+        &concerns,
+    );
+    issue
+        .edit_comment(&ctx.github, fcp.comment_id, &body)
+        .await?;
+    maybe_start(ctx, config, issue, &fcp, origin).await
+}
+
+async fn maybe_start(
+    ctx: &Context,
+    config: &FcpConfig,
+    issue: &Issue,
+    fcp: &Fcp,
+    origin: LabelChangeOrigin<'_>,
+) -> anyhow::Result<()> {
+    if fcp.state != fcps::PROPOSED {
+        return Ok(());
+    }
+    let reviews = fcps::reviews(&ctx.db, fcp.fcp_id).await?;
+    let concerns = fcps::concerns(&ctx.db, fcp.fcp_id).await?;
+    let reviewed = reviews.iter().filter(|r| r.reviewed).count();
+    if reviewed * 2 <= reviews.len()
+        || reviews.len() - reviewed > config.max_unreviewed
+        || concerns.iter().any(|c| c.resolved_by.is_none())
+    {
+        return Ok(());
+    }
+
+    fcps::set_state(&ctx.db, fcp.fcp_id, fcps::STARTED).await?;
+    let mut labels = issue
+        .labels()
+        .iter()
+        .filter(|l| l.name != PROPOSED_LABEL && l.name != FCP_LABEL)
+        .cloned()
+        .collect::<Vec<_>>();
+    labels.push(Label {
+        name: FCP_LABEL.to_owned(),
+    });
+    issue
+        .set_labels(&ctx.github, &ctx.db, origin, labels)
+        .await?;
+    issue
+        .post_comment(
+            &ctx.github,
+            &format!(
+                ":bell: **This is now entering its final comment period**, as per the \
+                 [review above]({}#issuecomment-{}). :bell:",
+                issue.html_url, fcp.comment_id
+            ),
+        )
+        .await
+}
+
+/// Finishes the final comment periods which are over.
+pub(super) async fn finish_fcps(ctx: &Context) -> anyhow::Result<()> {
+    for fcp in fcps::started_fcps(&ctx.db).await? {
+        if let Err(e) = finish_if_due(ctx, &fcp).await {
+            log::error!(
+                "failed to finish fcp of {}#{}: {:?}",
+                fcp.repo,
+                fcp.issue_number,
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn finish_if_due(ctx: &Context, fcp: &Fcp) -> anyhow::Result<()> {
+    let config = match crate::config::get(&ctx.github, &fcp.repo).await {
+        Ok(config) => config,
+        Err(_) => return Ok(()),
+    };
+    let config = match &config.fcp {
+        Some(config) => config,
+        None => return Ok(()),
+    };
+    let started_at = fcp.started_at.expect("started fcp");
+    let now: DateTime<FixedOffset> = Utc::now().into();
+    if now < started_at + Duration::days(config.duration_days.into()) {
+        return Ok(());
+    }
+
+    let issue = Repository {
+        full_name: fcp.repo.clone(),
+    }
+    .get_issue(&ctx.github, fcp.issue_number)
+    .await?;
+    let disposition_label = format!("disposition-{}", fcp.disposition);
+    let mut labels = issue
+        .labels()
+        .iter()
+        .filter(|l| l.name != PROPOSED_LABEL && l.name != FCP_LABEL)
+        .cloned()
+        .collect::<Vec<_>>();
+    for name in &[FINISHED_LABEL, disposition_label.as_str()] {
+        if !labels.iter().any(|l| l.name == *name) {
+            labels.push(Label {
+                name: name.to_string(),
+            });
+        }
+    }
+    let origin = LabelChangeOrigin {
+        handler: "fcp",
+        actor: None,
+        comment_url: None,
+    };
+    issue
+        .set_labels(&ctx.github, &ctx.db, origin, labels)
+        .await?;
+    issue
+        .post_comment(
+            &ctx.github,
+            &format!(
+                "The final comment period, with a disposition to **{}**, as per the \
+                 [review above]({}#issuecomment-{}), is now **complete**.",
+                fcp.disposition, issue.html_url, fcp.comment_id
+            ),
+        )
+        .await?;
+    fcps::set_state(&ctx.db, fcp.fcp_id, fcps::FINISHED).await
+}
+
+/// Renders the proposal comment.
+fn render(
+    ctx: &Context,
+    config: &FcpConfig,
+    proposer: &str,
+    disposition: &str,
+    reviews: &[Review],
+    concerns: &[Concern],
+) -> String {
+    let mut body = String::new();
+    let _ = writeln!(
+        body,
+        "Team member @{} has proposed to {} this. The next step is review by the rest of the \
+         tagged team members:\n",
+        proposer, disposition
+    );
+    for review in reviews {
+        let _ = writeln!(
+            body,
+            "- [{}] @{}",
+            if review.reviewed { "x" } else { " " },
+            review.reviewer
+        );
+    }
+    if !concerns.is_empty() {
+        let _ = writeln!(body, "\nConcerns:\n");
+        for concern in concerns {
+            let _ = match &concern.resolved_by {
+                Some(resolved_by) => {
+                    writeln!(body, "* ~~{}~~ resolved by @{}", concern.title, resolved_by)
+                }
+                None => writeln!(
+                    body,
+                    "* {} (raised by @{})",
+                    concern.title, concern.raised_by
+                ),
+            };
+        }
+    }
+    let _ = writeln!(
+        body,
+        "\nOnce a majority of reviewers approve (and at most {} approvals are outstanding), \
+         this will enter its final comment period of {} days. If you spot a major issue that \
+         hasn't been raised at any point in this process, please speak up!\n\n\
+         Reviewers can check their box, or comment `@{bot} fcp reviewed`. Concerns are raised \
+         with `@{bot} fcp concern <title>` and resolved with `@{bot} fcp resolve <title>`.",
+        config.max_unreviewed,
+        config.duration_days,
+        bot = ctx.username,
+    );
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_own_box_counts() {
+        let body = "- [x] @Alice\n- [x] @bob\n- [ ] @carol\n";
+        assert_eq!(checked_reviewer(body, "alice"), Some("Alice"));
+        assert_eq!(checked_reviewer(body, "bob"), Some("bob"));
+        assert_eq!(checked_reviewer(body, "carol"), None);
+        assert_eq!(checked_reviewer(body, "mallory"), None);
+    }
+}
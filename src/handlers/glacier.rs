@@ -1,10 +1,36 @@
 //! Allows team members to directly create a glacier PR with the code provided.
+//!
+//! The code is either downloaded from the given gist, or taken from the first fenced Rust code
+//! block of the comment or, failing that, of the issue description.
+//!
+//! Once an ICE is moved from `ices/` to `fixed/` in the glacier repository, the originating issue
+//! is told so and labeled as needing a regression test.
 
-use crate::{config::GlacierConfig, github::Event, handlers::Context};
+use crate::{
+    config::GlacierConfig,
+    db::glacier,
+    github::{Event, Label, LabelChangeOrigin, PushEvent, Repository},
+    handlers::Context,
+    interactions::ErrorComment,
+};
 
+use anyhow::Context as _;
 use octocrab::models::Object;
 use octocrab::params::repos::Reference;
+use octocrab::params::State;
+use parser::code_block::ColorCodeBlocks;
 use parser::command::glacier::GlacierCommand;
+use std::collections::BTreeSet;
+
+/// The maximum size of a reproducer, in bytes.
+const MAX_SOURCE_LEN: usize = 64 * 1024;
+
+/// The repository of the ICEs which were not added by the glacier command, and so have no
+/// recorded repository.
+const DEFAULT_ICE_REPO: &str = "rust-lang/rust";
+
+/// The label of issues whose ICE was fixed, but which still lack a regression test.
+const NEEDS_TEST_LABEL: &str = "E-needs-test";
 
 pub(super) async fn handle_command(
     ctx: &Context,
@@ -22,17 +48,72 @@ pub(super) async fn handle_command(
         return Ok(());
     };
 
-    let response = ctx.github.raw().get(&cmd.source).send().await?;
-    let body = response.text().await?;
+    let issue = event.issue().unwrap();
+    let body = match &cmd.source {
+        Some(source) => {
+            let bytes = match download(ctx.github.raw(), source).await? {
+                Some(bytes) => bytes,
+                None => return too_large(ctx, event).await,
+            };
+            match String::from_utf8(bytes) {
+                Ok(body) => body,
+                Err(_) => {
+                    let cmnt = ErrorComment::new(issue, "The reproducer is not valid UTF-8.");
+                    cmnt.post(&ctx.github).await?;
+                    return Ok(());
+                }
+            }
+        }
+        None => {
+            let code = event
+                .comment_body()
+                .and_then(first_rust_block)
+                .or_else(|| first_rust_block(&issue.body));
+            match code {
+                Some(code) if code.len() > MAX_SOURCE_LEN => return too_large(ctx, event).await,
+                Some(code) => code.to_owned(),
+                None => {
+                    let cmnt = ErrorComment::new(
+                        issue,
+                        "No reproducer found: either link a playground gist, or put the code \
+                        in a ```` ```rust ```` code block of the comment or the issue.",
+                    );
+                    cmnt.post(&ctx.github).await?;
+                    return Ok(());
+                }
+            }
+        }
+    };
 
-    let number = event.issue().unwrap().number;
+    let number = issue.number;
+    let repo = issue.repository().to_string();
     let user = event.user();
+    let branch = format!("triagebot-ice-{}", number);
 
     let octocrab = &ctx.octocrab;
 
     let fork = octocrab.repos("rustbot", "glacier");
     let base = octocrab.repos("rust-lang", "glacier");
 
+    let open_prs = octocrab
+        .pulls("rust-lang", "glacier")
+        .list()
+        .state(State::Open)
+        .head(format!("rustbot:{}", branch))
+        .send()
+        .await?;
+    if let Some(pr) = open_prs.items.first() {
+        let cmnt = ErrorComment::new(
+            issue,
+            format!(
+                "A glacier PR was already opened for this issue: {}",
+                pr.html_url
+            ),
+        );
+        cmnt.post(&ctx.github).await?;
+        return Ok(());
+    }
+
     let master = base
         .get_ref(&Reference::Branch("master".to_string()))
         .await?
@@ -44,32 +125,224 @@ pub(super) async fn handle_command(
         unreachable!()
     };
 
-    fork.create_ref(
-        &Reference::Branch(format!("triagebot-ice-{}", number)),
-        master,
-    )
-    .await?;
+    // Without an open PR, an existing branch is left over from a failed attempt, and is reset.
+    if fork
+        .create_ref(&Reference::Branch(branch.clone()), master.clone())
+        .await
+        .is_err()
+    {
+        let _: serde_json::Value = octocrab
+            .patch(
+                format!("repos/rustbot/glacier/git/refs/heads/{}", branch),
+                Some(&serde_json::json!({ "sha": master, "force": true })),
+            )
+            .await
+            .with_context(|| format!("resetting branch {}", branch))?;
+    }
+    let file = format!("{}.rs", number);
     fork.create_file(
-        format!("ices/{}.rs", number),
-        format!("Add ICE reproduction for issue rust-lang/rust#{}.", number),
+        format!("ices/{}", file),
+        format!("Add ICE reproduction for issue {}#{}.", repo, number),
         body,
     )
-    .branch(format!("triagebot-ice-{}", number))
+    .branch(branch.clone())
     .send()
     .await?;
 
-    octocrab
+    let pr = octocrab
         .pulls("rust-lang", "glacier")
         .create(
-            format!("ICE - rust-lang/rust#{}", number),
-            format!("rustbot:triagebot-ice-{}", number),
+            format!("ICE - {}#{}", repo, number),
+            format!("rustbot:{}", branch),
             "master",
         )
         .body(format!(
-            "Automatically created by @{} in issue rust-lang/rust#{}",
-            user.login, number
+            "Automatically created by @{} in issue {}#{}",
+            user.login, repo, number
         ))
         .send()
         .await?;
+    glacier::record_ice(&ctx.db, &repo, number, &file).await?;
+    issue
+        .post_comment(
+            &ctx.github,
+            &format!("Opened {} to track this ICE in glacier.", pr.html_url),
+        )
+        .await
+}
+
+/// Downloads the reproducer at `url`, or returns `None` if it is larger than `MAX_SOURCE_LEN`,
+/// without reading more than that.
+async fn download(client: &reqwest::Client, url: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    if response
+        .content_length()
+        .map_or(false, |len| len > MAX_SOURCE_LEN as u64)
+    {
+        return Ok(None);
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if bytes.len() + chunk.len() > MAX_SOURCE_LEN {
+            return Ok(None);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(Some(bytes))
+}
+
+/// Returns the contents of the first fenced Rust code block of `text`.
+fn first_rust_block(text: &str) -> Option<&str> {
+    ColorCodeBlocks::new(text)
+        .blocks()
+        .iter()
+        .find(|block| block.is_rust())
+        .map(|block| &text[block.contents.clone()])
+}
+
+async fn too_large(ctx: &Context, event: &Event) -> anyhow::Result<()> {
+    let cmnt = ErrorComment::new(
+        event.issue().unwrap(),
+        format!(
+            "The reproducer is too large: glacier accepts at most {} KiB.",
+            MAX_SOURCE_LEN / 1024
+        ),
+    );
+    cmnt.post(&ctx.github).await
+}
+
+pub(super) async fn handle(ctx: &Context, event: &Event) -> anyhow::Result<()> {
+    let push = match event {
+        Event::Push(push)
+            if push.repository.full_name == "rust-lang/glacier"
+                && push.git_ref == "refs/heads/master" =>
+        {
+            push
+        }
+        _ => return Ok(()),
+    };
+
+    let fixed_in = format!(
+        "https://github.com/rust-lang/glacier/compare/{}...{}",
+        push.before, push.after
+    );
+    for (issue_number, file) in fixed_ices(push) {
+        let repo = match glacier::record_fixed(&ctx.db, issue_number, &file, &fixed_in).await? {
+            Some(repo) => repo.unwrap_or_else(|| DEFAULT_ICE_REPO.to_owned()),
+            None => continue,
+        };
+        if let Err(e) = announce_fixed(ctx, &repo, issue_number, &file, &fixed_in).await {
+            log::error!(
+                "failed to announce the fix of {}#{}: {:?}",
+                repo,
+                issue_number,
+                e
+            );
+        }
+    }
     Ok(())
 }
+
+/// Returns the issue numbers and file names of the ICEs moved from `ices/` to `fixed/`.
+fn fixed_ices(push: &PushEvent) -> BTreeSet<(u64, String)> {
+    let mut fixed = BTreeSet::new();
+    for commit in &push.commits {
+        for path in &commit.added {
+            let file = match path.strip_prefix("fixed/") {
+                Some(file) => file,
+                None => continue,
+            };
+            if !commit
+                .removed
+                .iter()
+                .any(|p| *p == format!("ices/{}", file))
+            {
+                continue;
+            }
+            // Files are named after their issue, e.g. `12345.rs` or `12345-2.sh`.
+            let number = file.split(|c: char| !c.is_ascii_digit()).next().unwrap();
+            if let Ok(number) = number.parse() {
+                fixed.insert((number, file.to_owned()));
+            }
+        }
+    }
+    fixed
+}
+
+async fn announce_fixed(
+    ctx: &Context,
+    repo: &str,
+    issue_number: u64,
+    file: &str,
+    fixed_in: &str,
+) -> anyhow::Result<()> {
+    let issue = Repository {
+        full_name: repo.to_owned(),
+    }
+    .get_issue(&ctx.github, issue_number)
+    .await?;
+    issue
+        .post_comment(
+            &ctx.github,
+            &format!(
+                "This ICE no longer reproduces: glacier moved `{}` to the fixed ICEs in {}.\n\n\
+                The issue can be closed once a regression test is added.",
+                file, fixed_in
+            ),
+        )
+        .await?;
+
+    let mut labels = issue.labels().to_owned();
+    if !labels.iter().any(|l| l.name == NEEDS_TEST_LABEL) {
+        labels.push(Label {
+            name: NEEDS_TEST_LABEL.to_owned(),
+        });
+        let origin = LabelChangeOrigin {
+            handler: "glacier",
+            actor: None,
+            comment_url: None,
+        };
+        issue
+            .set_labels(&ctx.github, &ctx.db, origin, labels)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(commits: serde_json::Value) -> PushEvent {
+        serde_json::from_value(serde_json::json!({
+            "ref": "refs/heads/master",
+            "before": "a",
+            "after": "b",
+            "commits": commits,
+            "repository": { "full_name": "rust-lang/glacier" },
+            "sender": { "login": "alice", "id": 1 },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn fixed_ices_are_moves() {
+        let push = push(serde_json::json!([
+            {
+                "id": "1",
+                "added": ["fixed/12345.rs", "fixed/23456-2.sh", "fixed/README.md"],
+                "removed": ["ices/12345.rs", "ices/23456-2.sh", "ices/README.md"],
+            },
+            { "id": "2", "added": ["fixed/34567.rs"] },
+            { "id": "3", "added": ["ices/45678.rs"], "removed": ["fixed/45678.rs"] },
+        ]));
+        let fixed = fixed_ices(&push).into_iter().collect::<Vec<_>>();
+        assert_eq!(
+            fixed,
+            vec![
+                (12345, "12345.rs".to_owned()),
+                (23456, "23456-2.sh".to_owned()),
+            ]
+        );
+    }
+}
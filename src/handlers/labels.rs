@@ -0,0 +1,198 @@
+//! Purpose: Sync the labels declared in the `[labels]` section of `triagebot.toml` to the
+//! repository.
+//!
+//! Whenever `triagebot.toml` changes on the default branch, declared labels are created or updated
+//! to match their configured color and description. Labels listed in `renamed-from` are renamed;
+//! if the new label already exists, issues carrying the old label are moved over to the new one
+//! and the old label is deleted.
+//!
+//! Labels added or removed outside of triagebot (e.g. through the GitHub UI) are recorded in the
+//! label audit log; changes made by triagebot are recorded by `Issue::set_labels`.
+
+use crate::{
+    config::{self, LabelsConfig, CONFIG_FILE_NAME},
+    db::label_changes::{record_label_change, LabelChange},
+    github::{
+        Event, IssuesAction, IssuesEvent, Label, LabelChangeOrigin, Query, QueryKind, Repository,
+    },
+    handlers::Context,
+};
+use anyhow::Context as _;
+use chrono::Utc;
+
+/// Upper bound on the pages of issues moved from a renamed label, in case
+/// moving them keeps failing.
+const MAX_MIGRATION_ROUNDS: usize = 20;
+
+pub async fn handle(ctx: &Context, event: &Event) -> anyhow::Result<()> {
+    let push = match event {
+        Event::Push(push) => push,
+        Event::Issue(event) => return record_webhook_change(ctx, event).await,
+        _ => return Ok(()),
+    };
+    if !push.touches(CONFIG_FILE_NAME) {
+        return Ok(());
+    }
+    let repo = Repository {
+        full_name: event.repo_name().to_owned(),
+    };
+    let default_branch = repo.default_branch(&ctx.github).await?;
+    if push.git_ref != format!("refs/heads/{}", default_branch) {
+        return Ok(());
+    }
+
+    // The cached configuration predates this push, and is read from the branch pushed to.
+    let config = match config::refresh(&ctx.github, event.repo_name(), &default_branch).await {
+        Ok(config) => config,
+        Err(e) => {
+            log::warn!("not syncing labels of {}: {}", event.repo_name(), e);
+            return Ok(());
+        }
+    };
+    let labels = match &config.labels {
+        Some(labels) => labels,
+        None => return Ok(()),
+    };
+
+    let origin = LabelChangeOrigin {
+        handler: "labels",
+        actor: Some(&push.sender.login),
+        comment_url: None,
+    };
+    sync_labels(ctx, &repo, labels, origin).await
+}
+
+async fn record_webhook_change(ctx: &Context, event: &IssuesEvent) -> anyhow::Result<()> {
+    let label = match (&event.action, &event.label) {
+        (IssuesAction::Labeled, Some(label)) | (IssuesAction::Unlabeled, Some(label)) => label,
+        _ => return Ok(()),
+    };
+    // Our own changes were already recorded, with more context than the webhook has.
+    if event.sender.login == ctx.username {
+        return Ok(());
+    }
+
+    // The payload carries the labels after the change.
+    let labels_after = event
+        .issue
+        .labels()
+        .iter()
+        .map(|l| l.name.clone())
+        .collect::<Vec<_>>();
+    let mut labels_before = labels_after
+        .iter()
+        .filter(|l| **l != label.name)
+        .cloned()
+        .collect::<Vec<_>>();
+    if event.action == IssuesAction::Unlabeled {
+        labels_before.push(label.name.clone());
+    }
+    let change = LabelChange {
+        repo: event.repository.full_name.clone(),
+        issue_number: event.issue.number,
+        actor: Some(event.sender.login.clone()),
+        comment_url: None,
+        handler: None,
+        labels_before,
+        labels_after,
+        time: Utc::now().into(),
+    };
+    record_label_change(&ctx.db, &change).await
+}
+
+async fn sync_labels(
+    ctx: &Context,
+    repo: &Repository,
+    config: &LabelsConfig,
+    origin: LabelChangeOrigin<'_>,
+) -> anyhow::Result<()> {
+    let client = &ctx.github;
+    for (name, label_config) in &config.labels {
+        let desired = config.definition(name).expect("declared label");
+        match repo.get_label(client, name).await? {
+            Some(existing) => {
+                if !existing.color.eq_ignore_ascii_case(&desired.color)
+                    || existing.description != desired.description
+                {
+                    log::info!("updating label {} in {}", name, repo.full_name);
+                    repo.update_label(client, name, &desired).await?;
+                }
+            }
+            None => {
+                let mut renamed = false;
+                for old_name in &label_config.renamed_from {
+                    if repo.get_label(client, old_name).await?.is_some() {
+                        log::info!(
+                            "renaming label {} to {} in {}",
+                            old_name,
+                            name,
+                            repo.full_name
+                        );
+                        repo.update_label(client, old_name, &desired).await?;
+                        renamed = true;
+                        break;
+                    }
+                }
+                if !renamed {
+                    log::info!("creating label {} in {}", name, repo.full_name);
+                    repo.create_label(client, &desired).await?;
+                }
+            }
+        }
+
+        // Any old labels still around need their issues migrated.
+        for old_name in &label_config.renamed_from {
+            if repo.get_label(client, old_name).await?.is_some() {
+                migrate_issues(ctx, repo, old_name, name, origin)
+                    .await
+                    .with_context(|| format!("migrating label {} to {}", old_name, name))?;
+                repo.delete_label(client, old_name).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn migrate_issues(
+    ctx: &Context,
+    repo: &Repository,
+    from: &str,
+    to: &str,
+    origin: LabelChangeOrigin<'_>,
+) -> anyhow::Result<()> {
+    let query = Query {
+        kind: QueryKind::List,
+        filters: vec![("state", "all")],
+        include_labels: vec![from],
+        exclude_labels: vec![],
+    };
+    // Only one page of issues is returned at a time; moving the label off of
+    // them brings the next ones into the first page.
+    for _ in 0..MAX_MIGRATION_ROUNDS {
+        let issues = repo.get_issues(&ctx.github, &query).await?;
+        if issues.is_empty() {
+            return Ok(());
+        }
+        for issue in issues {
+            let mut labels = issue
+                .labels()
+                .iter()
+                .filter(|l| l.name != from)
+                .cloned()
+                .collect::<Vec<_>>();
+            if !labels.iter().any(|l| l.name == to) {
+                labels.push(Label {
+                    name: to.to_owned(),
+                });
+            }
+            issue
+                .set_labels(&ctx.github, &ctx.db, origin, labels)
+                .await?;
+        }
+    }
+    anyhow::bail!(
+        "issues labeled {} remain after {} rounds",
+        from,
+        MAX_MIGRATION_ROUNDS
+    )
+}
@@ -1,31 +1,92 @@
+//! Purpose: Run major change proposals (MCPs) through their lifecycle.
+//!
+//! A new proposal gets a Zulip topic, where the team is notified once it is seconded. Team members
+//! can raise concerns with `@rustbot concern <title>` and resolve them with
+//! `@rustbot resolve <title>`; both are mirrored into the Zulip topic, and the state of the
+//! proposal is kept up to date in a section of the issue description. Once the waiting period of
+//! a seconded proposal has elapsed with no open concerns, it is accepted by adding the accepted
+//! label. A proposal can be closed with `@rustbot withdraw`.
+//!
+//! Several independent processes can be configured per repository, each with its own labels,
+//! chat channel and Tera templates for the messages; the label of a proposal selects its process.
+
 use crate::{
-    config::MajorChangeConfig,
-    github::{Event, Issue, IssuesAction, IssuesEvent, Label},
+    chat,
+    config::{MajorChangeConfig, MajorChangeProcess},
+    db::major_changes::{self, MajorChange},
+    db::zulip_topics,
+    github::{Event, Issue, IssuesAction, IssuesEvent, Label, LabelChangeOrigin, Repository},
     handlers::Context,
-    interactions::ErrorComment,
+    interactions::{EditIssueBody, ErrorComment},
 };
 use anyhow::Context as _;
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use parser::command::concern::{ConcernAction, ConcernCommand};
 use parser::command::second::SecondCommand;
+use parser::command::withdraw::WithdrawCommand;
+use std::fmt::Write as _;
+
+const NEW_PROPOSAL_COMMENT: &str = "This issue is not meant to be used for technical discussion. \
+    There is a Zulip [stream] for that. Use this issue to leave procedural comments, such as \
+    volunteering to review, indicating that you second the proposal (or third, etc), or raising \
+    a concern that you would like to be addressed.\n\n[stream]: {{ topic_url }}";
+const NEW_PROPOSAL_ZULIP: &str = "A new proposal has been announced: \
+    [{{ title }} #{{ number }}]({{ url }}). It will be announced at the next meeting to try and \
+    draw attention to it, but usually MCPs are not discussed during triage meetings. If you \
+    think this would benefit from discussion amongst the team, consider proposing a design \
+    meeting.";
+const SECONDED_ZULIP: &str = "@*{{ zulip_ping }}*: Proposal [#{{ number }}]({{ comment_url }}) \
+    has been seconded, and will be approved in {{ waiting_period_days }} days if no objections \
+    are raised.";
+const ACCEPTED_ZULIP: &str = "This proposal has been accepted: [#{{ number }}]({{ url }}).";
+const AUTO_ACCEPTED_COMMENT: &str = "The waiting period of {{ waiting_period_days }} days has \
+    elapsed with no open concerns, so this proposal is now accepted.";
+const CONCERN_RAISED_ZULIP: &str = "@*{{ zulip_ping }}*: A concern was raised on proposal \
+    [#{{ number }}]({{ comment_url }}) by @{{ user }}: {{ concern }}";
+const CONCERN_RESOLVED_ZULIP: &str = "The concern \"{{ concern }}\" on proposal \
+    [#{{ number }}]({{ comment_url }}) was resolved by @{{ user }}.";
+const WITHDRAWN_ZULIP: &str =
+    "Proposal [#{{ number }}]({{ comment_url }}) has been withdrawn by @{{ user }}.";
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Invocation {
     NewProposal,
     AcceptedProposal,
+    ClosedProposal,
 }
 
 pub(super) fn parse_input(
     _ctx: &Context,
     event: &IssuesEvent,
-    _config: Option<&MajorChangeConfig>,
+    config: Option<&MajorChangeConfig>,
 ) -> Result<Option<Invocation>, String> {
+    let config = match config {
+        Some(config) => config,
+        None => return Ok(None),
+    };
+    let labeled = match (&event.action, &event.label) {
+        (IssuesAction::Labeled, Some(label)) => Some(&label.name),
+        _ => None,
+    };
+
     // If we were labeled with accepted, then issue that event
-    if event.action == IssuesAction::Labeled
-        && event
-            .label
-            .as_ref()
-            .map_or(false, |l| l.name == "major-change-accepted")
-    {
-        return Ok(Some(Invocation::AcceptedProposal));
+    if let Some(label) = labeled {
+        if config.processes().any(|p| p.accepted_label == *label) {
+            return Ok(Some(Invocation::AcceptedProposal));
+        }
+    }
+
+    if event.action == IssuesAction::Closed {
+        if let Some(process) = process_of(config, &event.issue) {
+            if !event
+                .issue
+                .labels()
+                .iter()
+                .any(|l| l.name == process.accepted_label)
+            {
+                return Ok(Some(Invocation::ClosedProposal));
+            }
+        }
     }
 
     // Opening an issue with a label assigned triggers both
@@ -34,17 +95,8 @@ pub(super) fn parse_input(
     // We want to treat reopened issues as new proposals but if the
     // issues is freshly opened, we only want to trigger once;
     // currently we do so on the label event.
-    if (event.action == IssuesAction::Reopened
-        && event
-            .issue
-            .labels()
-            .iter()
-            .any(|l| l.name == "major-change"))
-        || (event.action == IssuesAction::Labeled
-            && event
-                .label
-                .as_ref()
-                .map_or(false, |l| l.name == "major-change"))
+    if (event.action == IssuesAction::Reopened && process_of(config, &event.issue).is_some())
+        || labeled.map_or(false, |l| config.process_triggered_by(l).is_some())
     {
         return Ok(Some(Invocation::NewProposal));
     }
@@ -53,45 +105,102 @@ pub(super) fn parse_input(
     return Ok(None);
 }
 
-pub(super) async fn handle_input(
-    ctx: &Context,
-    config: &MajorChangeConfig,
-    event: &IssuesEvent,
-    cmd: Invocation,
-) -> anyhow::Result<()> {
-    if !event
-        .issue
+/// Returns the process of the proposal in `issue`, from its labels.
+fn process_of<'a>(config: &'a MajorChangeConfig, issue: &Issue) -> Option<&'a MajorChangeProcess> {
+    issue
         .labels()
         .iter()
-        .any(|l| l.name == "major-change")
-    {
+        .find_map(|l| config.process_triggered_by(&l.name))
+}
+
+/// Returns the process of the proposal in `issue`, or tells the user that it is not a proposal.
+async fn expect_process<'a>(
+    ctx: &Context,
+    config: &'a MajorChangeConfig,
+    issue: &Issue,
+) -> anyhow::Result<Option<&'a MajorChangeProcess>> {
+    let process = process_of(config, issue);
+    if process.is_none() {
+        let labels = config.processes().map(|p| &p.label[..]).collect::<Vec<_>>();
         let cmnt = ErrorComment::new(
-            &event.issue,
-            "This is not a major change (it lacks the `major-change` label).",
+            issue,
+            format!(
+                "This is not a major change (it lacks the `{}` label).",
+                labels.join("` or `")
+            ),
         );
         cmnt.post(&ctx.github).await?;
-        return Ok(());
     }
+    Ok(process)
+}
+
+/// The variables available to the templates of every message about `issue`; they are checked
+/// against the configured templates in `MajorChangeProcess::validate`.
+fn template_context(process: &MajorChangeProcess, issue: &Issue) -> tera::Context {
+    let mut context = tera::Context::new();
+    context.insert("number", &issue.number);
+    context.insert("title", &issue.title);
+    context.insert("url", &issue.html_url);
+    context.insert("zulip_ping", &process.zulip_ping);
+    context.insert("waiting_period_days", &process.waiting_period_days);
+    context
+}
+
+/// Renders the configured template, or the default one if none is configured.
+fn render(
+    template: &Option<String>,
+    default: &str,
+    context: &tera::Context,
+) -> anyhow::Result<String> {
+    let template = template.as_deref().unwrap_or(default);
+    tera::Tera::one_off(template, context, false).context("rendering major change template")
+}
+
+pub(super) async fn handle_input(
+    ctx: &Context,
+    config: &MajorChangeConfig,
+    event: &IssuesEvent,
+    cmd: Invocation,
+) -> anyhow::Result<()> {
+    // Issues can get the accepted label without being proposals of its process.
+    let process = match process_of(config, &event.issue) {
+        Some(process) => process,
+        None => return Ok(()),
+    };
+    let repo = event.issue.repository().to_string();
+    let context = template_context(process, &event.issue);
     let zulip_msg = match cmd {
-        Invocation::NewProposal => format!(
-            "A new proposal has been announced: [{} #{}]({}). It will be \
-            announced at the next meeting to try and draw attention to it, \
-            but usually MCPs are not discussed during triage meetings. If \
-            you think this would benefit from discussion amongst the \
-            team, consider proposing a design meeting.",
-            event.issue.title, event.issue.number, event.issue.html_url,
-        ),
-        Invocation::AcceptedProposal => format!(
-            "This proposal has been accepted: [#{}]({}).",
-            event.issue.number, event.issue.html_url,
-        ),
+        Invocation::NewProposal => {
+            major_changes::record_proposal(&ctx.db, &repo, event.issue.number).await?;
+            render(
+                &process.templates.new_proposal_zulip,
+                NEW_PROPOSAL_ZULIP,
+                &context,
+            )?
+        }
+        Invocation::AcceptedProposal => {
+            major_changes::finish(&ctx.db, &repo, event.issue.number, major_changes::ACCEPTED)
+                .await?;
+            update_status(ctx, process, &event.issue).await?;
+            render(&process.templates.accepted_zulip, ACCEPTED_ZULIP, &context)?
+        }
+        Invocation::ClosedProposal => {
+            return major_changes::finish(
+                &ctx.db,
+                &repo,
+                event.issue.number,
+                major_changes::CLOSED,
+            )
+            .await;
+        }
     };
     handle(
         ctx,
-        config,
+        process,
         &event.issue,
+        LabelChangeOrigin::from_issues_event("major_change", event),
         zulip_msg,
-        config.meeting_label.clone(),
+        process.meeting_label.clone(),
         cmd == Invocation::NewProposal,
     )
     .await
@@ -105,14 +214,10 @@ pub(super) async fn handle_command(
 ) -> anyhow::Result<()> {
     let issue = event.issue().unwrap();
 
-    if !issue.labels().iter().any(|l| l.name == "major-change") {
-        let cmnt = ErrorComment::new(
-            &issue,
-            "This is not a major change (it lacks the `major-change` label).",
-        );
-        cmnt.post(&ctx.github).await?;
-        return Ok(());
-    }
+    let process = match expect_process(ctx, config, issue).await? {
+        Some(process) => process,
+        None => return Ok(()),
+    };
 
     let is_team_member = event
         .user()
@@ -127,78 +232,330 @@ pub(super) async fn handle_command(
         return Ok(());
     }
 
-    let zulip_msg = format!(
-        "@*{}*: Proposal [#{}]({}) has been seconded, and will be approved in 10 days if no objections are raised.",
-        config.zulip_ping,
-        issue.number,
-        event.html_url().unwrap()
-    );
+    let repo = issue.repository().to_string();
+    if major_changes::record_second(&ctx.db, &repo, issue.number)
+        .await?
+        .is_none()
+    {
+        let cmnt = ErrorComment::new(
+            &issue,
+            "This proposal is already accepted or closed, so it can't be seconded anymore.",
+        );
+        cmnt.post(&ctx.github).await?;
+        return Ok(());
+    }
+    update_status(ctx, process, issue).await?;
+
+    let mut context = template_context(process, issue);
+    context.insert("comment_url", event.html_url().unwrap());
+    context.insert("user", &event.user().login);
+    let zulip_msg = render(&process.templates.seconded_zulip, SECONDED_ZULIP, &context)?;
 
     handle(
         ctx,
-        config,
+        process,
         issue,
+        LabelChangeOrigin::from_event("major_change", event),
         zulip_msg,
-        config.second_label.clone(),
+        process.second_label.clone(),
         false,
     )
     .await
 }
 
-async fn handle(
+pub(super) async fn handle_concern(
+    ctx: &Context,
+    config: &MajorChangeConfig,
+    event: &Event,
+    cmd: ConcernCommand,
+) -> anyhow::Result<()> {
+    let issue = event.issue().unwrap();
+
+    let process = match expect_process(ctx, config, issue).await? {
+        Some(process) => process,
+        None => return Ok(()),
+    };
+
+    let is_team_member = event
+        .user()
+        .is_team_member(&ctx.github)
+        .await
+        .ok()
+        .unwrap_or(false);
+
+    if !is_team_member {
+        let cmnt = ErrorComment::new(&issue, "Only team members can raise or resolve concerns.");
+        cmnt.post(&ctx.github).await?;
+        return Ok(());
+    }
+
+    let repo = issue.repository().to_string();
+    let user = &event.user().login;
+    let mut context = template_context(process, issue);
+    context.insert("comment_url", event.html_url().unwrap());
+    context.insert("user", user);
+    context.insert("concern", &cmd.title);
+    let zulip_msg = match cmd.action {
+        ConcernAction::Raise => {
+            major_changes::raise_concern(&ctx.db, &repo, issue.number, &cmd.title, user).await?;
+            render(
+                &process.templates.concern_raised_zulip,
+                CONCERN_RAISED_ZULIP,
+                &context,
+            )?
+        }
+        ConcernAction::Resolve => {
+            if !major_changes::resolve_concern(&ctx.db, &repo, issue.number, &cmd.title, user)
+                .await?
+            {
+                let cmnt = ErrorComment::new(
+                    &issue,
+                    format!("There is no open concern named `{}`.", cmd.title),
+                );
+                cmnt.post(&ctx.github).await?;
+                return Ok(());
+            }
+            render(
+                &process.templates.concern_resolved_zulip,
+                CONCERN_RESOLVED_ZULIP,
+                &context,
+            )?
+        }
+    };
+    update_status(ctx, process, issue).await?;
+    post_to_topic(ctx, process, issue, &zulip_msg).await
+}
+
+pub(super) async fn handle_withdraw(
     ctx: &Context,
     config: &MajorChangeConfig,
+    event: &Event,
+    _cmd: WithdrawCommand,
+) -> anyhow::Result<()> {
+    let issue = event.issue().unwrap();
+
+    let process = match expect_process(ctx, config, issue).await? {
+        Some(process) => process,
+        None => return Ok(()),
+    };
+
+    let user = event.user();
+    if user.login != issue.user.login
+        && !user.is_team_member(&ctx.github).await.ok().unwrap_or(false)
+    {
+        let cmnt = ErrorComment::new(
+            &issue,
+            "Only the author of the proposal and team members can withdraw it.",
+        );
+        cmnt.post(&ctx.github).await?;
+        return Ok(());
+    }
+
+    // Closing the issue moves the proposal to its final state.
+    issue.close(&ctx.github).await?;
+    let mut context = template_context(process, issue);
+    context.insert("comment_url", event.html_url().unwrap());
+    context.insert("user", &user.login);
+    let zulip_msg = render(
+        &process.templates.withdrawn_zulip,
+        WITHDRAWN_ZULIP,
+        &context,
+    )?;
+    post_to_topic(ctx, process, issue, &zulip_msg).await
+}
+
+/// Posts `msg` to the Zulip topic of the proposal.
+async fn post_to_topic(
+    ctx: &Context,
+    process: &MajorChangeProcess,
+    issue: &Issue,
+    msg: &str,
+) -> anyhow::Result<()> {
+    chat::backend(&process.channel(), ctx)?
+        .send(Some(&issue.zulip_topic()), msg)
+        .await
+        .context("zulip post failed")?;
+    Ok(())
+}
+
+/// Rewrites the status section of the issue description from the recorded state of the proposal.
+async fn update_status(
+    ctx: &Context,
+    process: &MajorChangeProcess,
     issue: &Issue,
+) -> anyhow::Result<()> {
+    let repo = issue.repository().to_string();
+    let proposal = major_changes::get(&ctx.db, &repo, issue.number).await?;
+    let concerns = major_changes::concerns(&ctx.db, &repo, issue.number).await?;
+
+    let mut text = String::new();
+    match &proposal {
+        Some(MajorChange {
+            accepted_at: Some(accepted_at),
+            ..
+        }) => writeln!(
+            text,
+            "This proposal was accepted on {}.",
+            accepted_at.format("%Y-%m-%d")
+        )?,
+        Some(MajorChange {
+            seconded_at: Some(seconded_at),
+            ..
+        }) => writeln!(
+            text,
+            "This proposal was seconded on {}, and will be accepted on {} if no concerns are \
+            open by then.",
+            seconded_at.format("%Y-%m-%d"),
+            (*seconded_at + Duration::days(process.waiting_period_days.into())).format("%Y-%m-%d")
+        )?,
+        _ => writeln!(text, "This proposal has not been seconded yet.")?,
+    }
+    if !concerns.is_empty() {
+        writeln!(text, "\nConcerns:")?;
+        for concern in &concerns {
+            match &concern.resolved_by {
+                Some(resolved_by) => writeln!(
+                    text,
+                    "- [x] ~~{}~~ (raised by {}, resolved by {})",
+                    concern.title, concern.raised_by, resolved_by
+                )?,
+                None => writeln!(
+                    text,
+                    "- [ ] {} (raised by {})",
+                    concern.title, concern.raised_by
+                )?,
+            }
+        }
+    }
+
+    EditIssueBody::new(issue, "MAJOR_CHANGE")
+        .apply(&ctx.github, text, ())
+        .await
+}
+
+/// Accepts the seconded proposals whose waiting period has elapsed with no open concerns.
+pub(super) async fn accept_proposals(ctx: &Context) -> anyhow::Result<()> {
+    for proposal in major_changes::seconded(&ctx.db).await? {
+        if let Err(e) = accept_if_due(ctx, &proposal).await {
+            log::error!(
+                "failed to accept major change {}#{}: {:?}",
+                proposal.repo,
+                proposal.issue_number,
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn accept_if_due(ctx: &Context, proposal: &MajorChange) -> anyhow::Result<()> {
+    let config = match crate::config::get(&ctx.github, &proposal.repo).await {
+        Ok(config) => config,
+        Err(_) => return Ok(()),
+    };
+    let config = match &config.major_change {
+        Some(config) => config,
+        None => return Ok(()),
+    };
+    if !major_changes::open_concerns(&ctx.db, &proposal.repo, proposal.issue_number)
+        .await?
+        .is_empty()
+    {
+        return Ok(());
+    }
+
+    let issue = Repository {
+        full_name: proposal.repo.clone(),
+    }
+    .get_issue(&ctx.github, proposal.issue_number)
+    .await?;
+    let process = match process_of(config, &issue) {
+        Some(process) => process,
+        None => return Ok(()),
+    };
+    let seconded_at = proposal.seconded_at.expect("seconded proposal");
+    let now: DateTime<FixedOffset> = Utc::now().into();
+    if now < seconded_at + Duration::days(process.waiting_period_days.into()) {
+        return Ok(());
+    }
+
+    // Adding the label triggers the announcement of the acceptance on Zulip and the update of the
+    // status section, like a manual acceptance would.
+    let mut labels = issue.labels().to_owned();
+    if !labels.iter().any(|l| l.name == process.accepted_label) {
+        labels.push(Label {
+            name: process.accepted_label.clone(),
+        });
+    }
+    let origin = LabelChangeOrigin {
+        handler: "major_change",
+        actor: None,
+        comment_url: None,
+    };
+    issue
+        .set_labels(&ctx.github, &ctx.db, origin, labels)
+        .await?;
+    let comment = render(
+        &process.templates.auto_accepted_comment,
+        AUTO_ACCEPTED_COMMENT,
+        &template_context(process, &issue),
+    )?;
+    issue.post_comment(&ctx.github, &comment).await?;
+    major_changes::finish(
+        &ctx.db,
+        &proposal.repo,
+        proposal.issue_number,
+        major_changes::ACCEPTED,
+    )
+    .await
+}
+
+async fn handle(
+    ctx: &Context,
+    process: &MajorChangeProcess,
+    issue: &Issue,
+    origin: LabelChangeOrigin<'_>,
     zulip_msg: String,
     label_to_add: String,
     new_proposal: bool,
 ) -> anyhow::Result<()> {
     let mut labels = issue.labels().to_owned();
     labels.push(Label { name: label_to_add });
-    let github_req = issue.set_labels(&ctx.github, labels);
-
-    // Concatenate the issue title and the topic reference, truncating such that
-    // the overall length does not exceed 60 characters (a Zulip limitation).
-    let topic_ref = issue.zulip_topic_reference();
-    // Skip chars until the last characters that can be written:
-    // Maximum 60, minus the reference, minus the elipsis and the space
-    let mut chars = issue
-        .title
-        .char_indices()
-        .skip(60 - topic_ref.chars().count() - 2);
-    let zulip_topic = match chars.next() {
-        Some((len, _)) if chars.next().is_some() => {
-            format!("{}… {}", &issue.title[..len], topic_ref)
-        }
-        _ => format!("{} {}", issue.title, topic_ref),
-    };
+    let github_req = issue.set_labels(&ctx.github, &ctx.db, origin, labels);
 
-    let zulip_req = crate::zulip::MessageApiRequest {
-        recipient: crate::zulip::Recipient::Stream {
-            id: config.zulip_stream,
-            topic: &zulip_topic,
-        },
-        content: &zulip_msg,
-    };
+    let zulip_topic = issue.zulip_topic();
+    let channel = process.channel();
+    let zulip = chat::backend(&channel, ctx)?;
 
     if new_proposal {
-        let topic_url = zulip_req.url();
-        let comment = format!(
-            "This issue is not meant to be used for technical discussion. \
-        There is a Zulip [stream] for that. Use this issue to leave \
-        procedural comments, such as volunteering to review, indicating that you \
-        second the proposal (or third, etc), or raising a concern that you would \
-        like to be addressed. \
-        \n\n[stream]: {}",
-            topic_url
+        let mut context = template_context(process, issue);
+        context.insert(
+            "topic_url",
+            &zulip.permalink(Some(&zulip_topic)).unwrap_or_default(),
         );
+        let comment = render(
+            &process.templates.new_proposal_comment,
+            NEW_PROPOSAL_COMMENT,
+            &context,
+        )?;
         issue
             .post_comment(&ctx.github, &comment)
             .await
             .context("post major change comment")?;
+        // The comment points to the topic already.
+        if let Some(stream) = channel.zulip_stream() {
+            zulip_topics::link(
+                &ctx.db,
+                stream,
+                &zulip_topic,
+                &issue.repository().to_string(),
+                issue.number,
+            )
+            .await?;
+        }
     }
 
-    let zulip_req = zulip_req.send(&ctx.github.raw());
+    let zulip_req = zulip.send(Some(&zulip_topic), &zulip_msg);
 
     let (gh_res, zulip_res) = futures::join!(github_req, zulip_req);
     zulip_res.context("zulip post failed")?;
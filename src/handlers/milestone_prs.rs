@@ -41,9 +41,7 @@ pub async fn handle(ctx: &Context, event: &Event) -> anyhow::Result<()> {
     };
 
     // Fetch the version from the upstream repository.
-    let version = if let Some(version) = get_version_standalone(ctx, merge_sha).await? {
-        version
-    } else if let Some(version) = get_version_channelrs(ctx, merge_sha).await? {
+    let version = if let Some(version) = get_version(ctx, merge_sha).await? {
         version
     } else {
         log::error!("could not find the version of {:?}", merge_sha);
@@ -66,6 +64,15 @@ pub async fn handle(ctx: &Context, event: &Event) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Fetches the version of rust-lang/rust at `git_ref`, a commit or branch.
+pub(super) async fn get_version(ctx: &Context, git_ref: &str) -> anyhow::Result<Option<String>> {
+    if let Some(version) = get_version_standalone(ctx, git_ref).await? {
+        Ok(Some(version))
+    } else {
+        get_version_channelrs(ctx, git_ref).await
+    }
+}
+
 async fn get_version_standalone(ctx: &Context, merge_sha: &str) -> anyhow::Result<Option<String>> {
     let resp = ctx
         .github
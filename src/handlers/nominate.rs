@@ -1,11 +1,23 @@
 //! Purpose: Allow team members to nominate issues or PRs.
+//!
+//! Nominations are recorded in the database along with their reason, which is shown in the
+//! meeting agendas. Once discussed, a team member resolves the nominations of a team with
+//! `@rustbot resolve-nomination <team>: <summary>`, which posts the summary and removes
+//! `I-nominated` when no other team's nomination is pending. Resolving a backport nomination
+//! declines the backport and removes `beta-nominated` or `stable-nominated` the same way.
+//!
+//! Pull requests can also be nominated for backporting to beta or stable, and the release team
+//! accepts these with `@rustbot beta-accept` or `@rustbot stable-accept`; the `backport` handler
+//! takes it from there.
 
 use crate::{
     config::NominateConfig,
-    github::{self, Event},
-    handlers::Context,
+    db::nominations::{self, Nomination},
+    github::{self, Event, LabelChangeOrigin},
+    handlers::{backport, Context},
     interactions::ErrorComment,
 };
+use chrono::Utc;
 use parser::command::nominate::{NominateCommand, Style};
 
 pub(super) async fn handle_command(
@@ -33,27 +45,39 @@ pub(super) async fn handle_command(
         return Ok(());
     }
 
+    if cmd.style == Style::Resolve {
+        return resolve(ctx, config, event, cmd).await;
+    }
+
+    let approved_channel = match cmd.style {
+        Style::BetaApprove => Some(nominations::BETA),
+        Style::StableApprove => Some(nominations::STABLE),
+        _ => None,
+    };
     let mut issue_labels = event.issue().unwrap().labels().to_owned();
-    if cmd.style == Style::BetaApprove {
-        if !issue_labels.iter().any(|l| l.name == "beta-nominated") {
+    if let Some(channel) = approved_channel {
+        if !issue_labels
+            .iter()
+            .any(|l| l.name == format!("{}-nominated", channel))
+        {
             let cmnt = ErrorComment::new(
                 &event.issue().unwrap(),
                 format!(
-                    "This pull request is not beta-nominated, so it cannot be approved yet.\
-                     Perhaps try to beta-nominate it by using `@{} beta-nominate <team>`?",
-                    ctx.username,
+                    "This pull request is not {channel}-nominated, so it cannot be approved yet.\
+                     Perhaps try to {channel}-nominate it by using `@{bot} {channel}-nominate <team>`?",
+                    channel = channel,
+                    bot = ctx.username,
                 ),
             );
             cmnt.post(&ctx.github).await?;
             return Ok(());
         }
 
-        // Add the beta-accepted label, but don't attempt to remove beta-nominated or the team
-        // label.
-        if !issue_labels.iter().any(|l| l.name == "beta-accepted") {
-            issue_labels.push(github::Label {
-                name: "beta-accepted".into(),
-            });
+        // Add the accepted label, but don't attempt to remove the nominated or the team label;
+        // the nominated label is removed once the backport is merged.
+        let accepted = format!("{}-accepted", channel);
+        if !issue_labels.iter().any(|l| l.name == accepted) {
+            issue_labels.push(github::Label { name: accepted });
         }
     } else {
         if !config.teams.contains_key(&cmd.team) {
@@ -75,9 +99,10 @@ pub(super) async fn handle_command(
         }
 
         let style_label = match cmd.style {
-            Style::Decision => "I-nominated",
-            Style::Beta => "beta-nominated",
-            Style::BetaApprove => unreachable!(),
+            Style::Decision => nominated_label(nominations::DECISION),
+            Style::Beta => nominated_label(nominations::BETA),
+            Style::Stable => nominated_label(nominations::STABLE),
+            Style::BetaApprove | Style::StableApprove | Style::Resolve => unreachable!(),
         };
         if !issue_labels.iter().any(|l| l.name == style_label) {
             issue_labels.push(github::Label {
@@ -90,9 +115,129 @@ pub(super) async fn handle_command(
         event
             .issue()
             .unwrap()
-            .set_labels(&ctx.github, issue_labels)
+            .set_labels(
+                &ctx.github,
+                &ctx.db,
+                LabelChangeOrigin::from_event("nominate", event),
+                issue_labels,
+            )
+            .await?;
+    }
+
+    if let Some(channel) = approved_channel {
+        return backport::accept(ctx, event, channel).await;
+    }
+
+    let kind = match cmd.style {
+        Style::Decision => nominations::DECISION,
+        Style::Beta => nominations::BETA,
+        Style::Stable => nominations::STABLE,
+        Style::BetaApprove | Style::StableApprove | Style::Resolve => unreachable!(),
+    };
+    let issue = event.issue().unwrap();
+    nominations::record_nomination(
+        &ctx.db,
+        &Nomination {
+            repo: issue.repository().to_string(),
+            issue_number: issue.number,
+            team: cmd.team,
+            kind: kind.to_owned(),
+            nominator: event.user().login.clone(),
+            reason: cmd.reason,
+            time: Utc::now().into(),
+        },
+    )
+    .await?;
+    if kind != nominations::DECISION {
+        backport::nominate(ctx, event, kind).await?;
+    }
+
+    Ok(())
+}
+
+async fn resolve(
+    ctx: &Context,
+    config: &NominateConfig,
+    event: &Event,
+    cmd: NominateCommand,
+) -> anyhow::Result<()> {
+    let issue = event.issue().unwrap();
+    if !config.teams.contains_key(&cmd.team) {
+        let cmnt = ErrorComment::new(
+            &issue,
+            format!(
+                "This team (`{}`) cannot be nominated for via this command;\
+                 it may need to be added to `triagebot.toml` on the master branch.",
+                cmd.team,
+            ),
+        );
+        cmnt.post(&ctx.github).await?;
+        return Ok(());
+    }
+
+    let repo = issue.repository().to_string();
+    let user = &event.user().login;
+    let summary = cmd.reason.expect("resolutions have a summary");
+    let resolved = nominations::resolve_nominations(
+        &ctx.db,
+        &repo,
+        issue.number,
+        None,
+        Some(&cmd.team),
+        user,
+        &summary,
+    )
+    .await?;
+    if resolved.is_empty() {
+        let cmnt = ErrorComment::new(
+            &issue,
+            format!("There is no open nomination for the `{}` team.", cmd.team),
+        );
+        cmnt.post(&ctx.github).await?;
+        return Ok(());
+    }
+    issue
+        .post_comment(
+            &ctx.github,
+            &format!(
+                "The nomination for the `{}` team was resolved by @{}:\n\n> {}",
+                cmd.team, user, summary
+            ),
+        )
+        .await?;
+
+    // The label stays while other teams still have to discuss the issue.
+    let mut labels = issue.labels().to_owned();
+    for kind in &resolved {
+        if nominations::has_open_nominations(&ctx.db, &repo, issue.number, kind).await? {
+            continue;
+        }
+        // Accepting a backport resolves its nominations, so these were declined.
+        if kind != nominations::DECISION {
+            backport::decline(ctx, &repo, issue.number, kind).await?;
+        }
+        let label = nominated_label(kind);
+        labels.retain(|l| l.name != label);
+    }
+    if &labels[..] != issue.labels() {
+        issue
+            .set_labels(
+                &ctx.github,
+                &ctx.db,
+                LabelChangeOrigin::from_event("nominate", event),
+                labels,
+            )
             .await?;
     }
 
     Ok(())
 }
+
+/// The label of open nominations of `kind`.
+fn nominated_label(kind: &str) -> &'static str {
+    match kind {
+        nominations::BETA => "beta-nominated",
+        nominations::STABLE => "stable-nominated",
+        _ => "I-nominated",
+    }
+}
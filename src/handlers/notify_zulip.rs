@@ -1,16 +1,61 @@
+//! Purpose: Post to Zulip (or another chat backend) when labels are added to or removed from
+//! issues, and when labeled issues go through their lifecycle.
+//!
+//! The configuration is keyed by label. Label changes are matched against the keys exactly; the
+//! other events (an issue being opened, closed, reopened or merged, or a pull request being
+//! reviewed) are matched against the labels of the issue, with the keys being globs.
+//!
+//! The topic and messages are Tera templates, with the following variables: `number`, `title`,
+//! `url`, `author`, `labels`, `assignees`, `repo`, `label` (the label which changed or matched),
+//! `actor` (who triggered the event), `review_state` (for reviews) and `excerpt` (the start of the
+//! issue description). The `{number}` and `{title}` placeholders of older configurations still
+//! work.
+
 use crate::{
-    config::NotifyZulipConfig,
+    chat,
+    config::{ChannelConfig, NotifyZulipConfig, NotifyZulipLabelConfig},
     github::{IssuesAction, IssuesEvent},
     handlers::Context,
+    zulip,
 };
+use anyhow::Context as _;
+
+/// The maximum length of the `excerpt` template variable.
+const EXCERPT_LEN: usize = 300;
 
 pub(super) struct NotifyZulipInput {
-    notification_type: NotificationType,
+    /// The notifications to post: the key of their configuration, the label which triggered
+    /// them and their kind.
+    notifications: Vec<(String, String, NotificationType)>,
 }
 
+#[derive(Copy, Clone)]
 pub(super) enum NotificationType {
     Labeled,
     Unlabeled,
+    Opened,
+    Closed,
+    Reopened,
+    Merged,
+    Reviewed,
+}
+
+impl NotificationType {
+    fn message(self, config: &NotifyZulipLabelConfig) -> Option<&String> {
+        match self {
+            NotificationType::Labeled => config.message_on_add.as_ref(),
+            NotificationType::Unlabeled => config.message_on_remove.as_ref(),
+            NotificationType::Opened => config.message_on_open.as_ref(),
+            NotificationType::Closed => config.message_on_close.as_ref(),
+            NotificationType::Reopened => config.message_on_reopen.as_ref(),
+            // Merging a pull request closes it.
+            NotificationType::Merged => config
+                .message_on_merge
+                .as_ref()
+                .or_else(|| config.message_on_close.as_ref()),
+            NotificationType::Reviewed => config.message_on_review.as_ref(),
+        }
+    }
 }
 
 pub(super) fn parse_input(
@@ -18,40 +63,87 @@ pub(super) fn parse_input(
     event: &IssuesEvent,
     config: Option<&NotifyZulipConfig>,
 ) -> Result<Option<NotifyZulipInput>, String> {
-    if let IssuesAction::Labeled | IssuesAction::Unlabeled = event.action {
+    let config = match config {
+        Some(config) => config,
+        None => return Ok(None),
+    };
+    let notification_type = match event.action {
+        IssuesAction::Labeled => NotificationType::Labeled,
+        IssuesAction::Unlabeled => NotificationType::Unlabeled,
+        IssuesAction::Opened => NotificationType::Opened,
+        IssuesAction::Closed if event.issue.merged => NotificationType::Merged,
+        IssuesAction::Closed => NotificationType::Closed,
+        IssuesAction::Reopened => NotificationType::Reopened,
+        IssuesAction::ReviewSubmitted => NotificationType::Reviewed,
+        _ => return Ok(None),
+    };
+
+    let mut notifications = Vec::new();
+    if let NotificationType::Labeled | NotificationType::Unlabeled = notification_type {
         let applied_label = &event.label.as_ref().expect("label").name;
-        if let Some(config) = config.and_then(|c| c.labels.get(applied_label)) {
-            for label in &config.required_labels {
-                let pattern = match glob::Pattern::new(label) {
-                    Ok(pattern) => pattern,
-                    Err(err) => {
-                        log::error!("Invalid glob pattern: {}", err);
-                        continue;
-                    }
-                };
-                if !event
-                    .issue
-                    .labels()
-                    .iter()
-                    .any(|l| pattern.matches(&l.name))
-                {
-                    // Issue misses a required label, ignore this event
-                    return Ok(None);
+        if let Some(label_config) = config.labels.get(applied_label) {
+            if notification_type.message(label_config).is_some()
+                && has_required_labels(label_config, event)
+            {
+                notifications.push((
+                    applied_label.clone(),
+                    applied_label.clone(),
+                    notification_type,
+                ));
+            }
+        }
+    } else {
+        for (key, label_config) in &config.labels {
+            if notification_type.message(label_config).is_none() {
+                continue;
+            }
+            let pattern = match glob::Pattern::new(key) {
+                Ok(pattern) => pattern,
+                Err(err) => {
+                    log::error!("Invalid glob pattern: {}", err);
+                    continue;
+                }
+            };
+            let label = event
+                .issue
+                .labels()
+                .iter()
+                .find(|l| pattern.matches(&l.name));
+            if let Some(label) = label {
+                if has_required_labels(label_config, event) {
+                    notifications.push((key.clone(), label.name.clone(), notification_type));
                 }
             }
+        }
+    }
 
-            if event.action == IssuesAction::Labeled && config.message_on_add.is_some() {
-                return Ok(Some(NotifyZulipInput {
-                    notification_type: NotificationType::Labeled,
-                }));
-            } else if config.message_on_remove.is_some() {
-                return Ok(Some(NotifyZulipInput {
-                    notification_type: NotificationType::Unlabeled,
-                }));
+    if notifications.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(NotifyZulipInput { notifications }))
+    }
+}
+
+fn has_required_labels(config: &NotifyZulipLabelConfig, event: &IssuesEvent) -> bool {
+    for label in &config.required_labels {
+        let pattern = match glob::Pattern::new(label) {
+            Ok(pattern) => pattern,
+            Err(err) => {
+                log::error!("Invalid glob pattern: {}", err);
+                continue;
             }
+        };
+        if !event
+            .issue
+            .labels()
+            .iter()
+            .any(|l| pattern.matches(&l.name))
+        {
+            // Issue misses a required label, ignore this event
+            return false;
         }
     }
-    Ok(None)
+    true
 }
 
 pub(super) async fn handle_input<'a>(
@@ -60,14 +152,22 @@ pub(super) async fn handle_input<'a>(
     event: &IssuesEvent,
     input: NotifyZulipInput,
 ) -> anyhow::Result<()> {
-    let config = config
-        .labels
-        .get(&event.label.as_ref().unwrap().name)
-        .unwrap();
-
-    let mut topic = config.topic.clone();
-    topic = topic.replace("{number}", &event.issue.number.to_string());
-    topic = topic.replace("{title}", &event.issue.title);
+    for (key, label, notification_type) in input.notifications {
+        let config = &config.labels[&key];
+        notify(ctx, config, event, &label, notification_type).await?;
+    }
+    Ok(())
+}
+
+async fn notify(
+    ctx: &Context,
+    config: &NotifyZulipLabelConfig,
+    event: &IssuesEvent,
+    label: &str,
+    notification_type: NotificationType,
+) -> anyhow::Result<()> {
+    let context = template_context(event, label);
+    let mut topic = render(&config.topic, &context)?;
     // Truncate to 60 chars (a Zulip limitation)
     let mut chars = topic.char_indices().skip(59);
     if let (Some((len, _)), Some(_)) = (chars.next(), chars.next()) {
@@ -75,22 +175,57 @@ pub(super) async fn handle_input<'a>(
         topic.push('…');
     }
 
-    let mut msg = match input.notification_type {
-        NotificationType::Labeled => config.message_on_add.as_ref().unwrap().clone(),
-        NotificationType::Unlabeled => config.message_on_remove.as_ref().unwrap().clone(),
-    };
+    let msg = render(notification_type.message(config).unwrap(), &context)?;
 
-    msg = msg.replace("{number}", &event.issue.number.to_string());
-    msg = msg.replace("{title}", &event.issue.title);
-
-    let zulip_req = crate::zulip::MessageApiRequest {
-        recipient: crate::zulip::Recipient::Stream {
-            id: config.zulip_stream,
-            topic: &topic,
-        },
-        content: &msg,
-    };
-    zulip_req.send(&ctx.github.raw()).await?;
+    let channel = config.channel();
+    chat::backend(&channel, ctx)?
+        .send(Some(&topic), &msg)
+        .await?;
+    // Topics shared by several issues, e.g. `nominations`, are not linked.
+    if let ChannelConfig::Zulip { stream } = channel {
+        if topic == event.issue.zulip_topic() {
+            zulip::link_topic(ctx, &event.issue, stream).await?;
+        }
+    }
 
     Ok(())
 }
+
+/// The variables available to the topic and message templates.
+// Keep `NotifyZulipLabelConfig::sample_context` in sync with the variables set here.
+fn template_context(event: &IssuesEvent, label: &str) -> tera::Context {
+    let issue = &event.issue;
+    let mut context = tera::Context::new();
+    context.insert("number", &issue.number);
+    context.insert("title", &issue.title);
+    context.insert("url", &issue.html_url);
+    context.insert("author", &issue.user.login);
+    context.insert(
+        "labels",
+        &issue.labels().iter().map(|l| &l.name).collect::<Vec<_>>(),
+    );
+    context.insert(
+        "assignees",
+        &issue.assignees.iter().map(|u| &u.login).collect::<Vec<_>>(),
+    );
+    context.insert("repo", &event.repository.full_name);
+    context.insert("label", label);
+    context.insert("actor", &event.sender.login);
+    context.insert("review_state", &event.review_state);
+    context.insert("excerpt", &excerpt(&issue.body));
+    context
+}
+
+/// The first paragraph of `body`, shortened to `EXCERPT_LEN` characters.
+fn excerpt(body: &str) -> String {
+    let paragraph = body.trim().split("\n\n").next().unwrap_or("").trim();
+    match paragraph.char_indices().nth(EXCERPT_LEN) {
+        Some((len, _)) => format!("{}…", &paragraph[..len]),
+        None => paragraph.to_owned(),
+    }
+}
+
+fn render(template: &str, context: &tera::Context) -> anyhow::Result<String> {
+    let template = NotifyZulipLabelConfig::tera_source(template);
+    tera::Tera::one_off(&template, context, false).context("rendering notify-zulip template")
+}
@@ -1,16 +1,28 @@
 //! Purpose: Allow any user to ping a pre-selected group of people on GitHub via comments.
 //!
 //! The set of "teams" which can be pinged is intentionally restricted via configuration.
+//! A team can be limited to one ping per issue within its `cooldown-hours`, and to at most
+//! `max-per-day` pings in any 24 hours, across all issues. Members who opted out of a team's pings
+//! (through the `ping-opt-out` Zulip command) are left out of the `cc` list.
+//!
+//! Groups are either teams of the team repository, or list their `members` inline. Anyone can be
+//! added to a group's pings with `@rustbot ping subscribe <group>`, and leave them (or opt out, for
+//! members) with `@rustbot ping unsubscribe <group>`; to reduce notification noise, these commands
+//! give no feedback. A group can also notify a Zulip stream and mark the issue with a
+//! `pinged-label` whenever it is pinged.
 //!
 //! Parsing is done in the `parser::command::ping` module.
 
 use crate::{
     config::PingConfig,
-    github::{self, Event},
+    db::pings,
+    github::{self, Event, LabelChangeOrigin},
     handlers::Context,
     interactions::ErrorComment,
+    zulip::{MessageApiRequest, Recipient},
 };
-use parser::command::ping::PingCommand;
+use chrono::{Duration, Utc};
+use parser::command::ping::{PingAction, PingCommand};
 
 pub(super) async fn handle_command(
     ctx: &Context,
@@ -18,6 +30,10 @@ pub(super) async fn handle_command(
     event: &Event,
     team_name: PingCommand,
 ) -> anyhow::Result<()> {
+    if team_name.action != PingAction::Ping {
+        return handle_subscription(ctx, config, event, team_name).await;
+    }
+
     let is_team_member = if let Err(_) | Ok(false) = event.user().is_team_member(&ctx.github).await
     {
         false
@@ -49,10 +65,12 @@ pub(super) async fn handle_command(
             return Ok(());
         }
     };
-    let team = github::get_team(&ctx.github, &gh_team).await?;
-    let team = match team {
-        Some(team) => team,
-        None => {
+    let team = match &config.members {
+        Some(_) => None,
+        None => github::get_team(&ctx.github, &gh_team).await?,
+    };
+    match (&config.members, &team) {
+        (None, None) => {
             let cmnt = ErrorComment::new(
                 &event.issue().unwrap(),
                 format!(
@@ -63,34 +81,113 @@ pub(super) async fn handle_command(
             cmnt.post(&ctx.github).await?;
             return Ok(());
         }
-    };
+        _ => {}
+    }
 
-    if let Some(label) = config.label.clone() {
-        let issue_labels = event.issue().unwrap().labels();
-        if !issue_labels.iter().any(|l| l.name == label) {
-            let mut issue_labels = issue_labels.to_owned();
-            issue_labels.push(github::Label { name: label });
-            event
-                .issue()
-                .unwrap()
-                .set_labels(&ctx.github, issue_labels)
-                .await?;
+    let issue = event.issue().unwrap();
+    let repo = issue.repository().to_string();
+    let now = Utc::now();
+    if config.cooldown_hours > 0 {
+        let last = pings::last_team_ping(&ctx.db, &repo, issue.number, gh_team).await?;
+        if let Some(last) = last {
+            if now.signed_duration_since(last) < Duration::hours(config.cooldown_hours.into()) {
+                let cmnt = ErrorComment::new(
+                    &issue,
+                    format!(
+                        "This team (`{}`) was already pinged on this issue in the last {} hours.",
+                        gh_team, config.cooldown_hours,
+                    ),
+                );
+                cmnt.post(&ctx.github).await?;
+                return Ok(());
+            }
+        }
+    }
+    if let Some(max_per_day) = config.max_per_day {
+        let since = (now - Duration::days(1)).into();
+        if pings::team_pings_since(&ctx.db, gh_team, since).await? >= max_per_day {
+            let cmnt = ErrorComment::new(
+                &issue,
+                format!(
+                    "This team (`{}`) has already been pinged {} times in the last 24 hours; \
+                    please try again later.",
+                    gh_team, max_per_day,
+                ),
+            );
+            cmnt.post(&ctx.github).await?;
+            return Ok(());
         }
     }
 
+    let mut issue_labels = issue.labels().to_owned();
+    for label in config.label.iter().chain(config.pinged_label.iter()) {
+        if !issue_labels.iter().any(|l| l.name == *label) {
+            issue_labels.push(github::Label {
+                name: label.clone(),
+            });
+        }
+    }
+    if issue_labels != issue.labels() {
+        issue
+            .set_labels(
+                &ctx.github,
+                &ctx.db,
+                LabelChangeOrigin::from_event("ping", event),
+                issue_labels,
+            )
+            .await?;
+    }
+
+    let opted_out = pings::opted_out_users(&ctx.db, gh_team).await?;
     let mut users = Vec::new();
 
-    if let Some(gh) = team.github {
-        let repo = event.issue().expect("has issue").repository();
+    // A GitHub team mention reaches everyone in it, so members who opted out
+    // can only be left out by mentioning the others individually.
+    if let Some(members) = &config.members {
+        for member in members {
+            // Inline members need not be in the team repository, so GitHub is asked for their ID.
+            let id = if opted_out.is_empty() {
+                None
+            } else {
+                let user = github::User {
+                    login: member.clone(),
+                    id: None,
+                };
+                match user.get_github_id(&ctx.github).await {
+                    Ok(id) => Some(id),
+                    Err(e) => {
+                        log::warn!("failed to get the id of {}: {:?}", member, e);
+                        None
+                    }
+                }
+            };
+            if !id.map_or(false, |id| opted_out.contains(&id)) {
+                users.push(format!("@{}", member));
+            }
+        }
+    } else if let (Some(gh), true) = (
+        team.as_ref().and_then(|t| t.github.as_ref()),
+        opted_out.is_empty(),
+    ) {
+        let repo = issue.repository();
         // Ping all github teams associated with this team repo team that are in this organization.
         // We cannot ping across organizations, but this should not matter, as teams should be
         // sync'd to the org for which triagebot is configured.
         for gh_team in gh.teams.iter().filter(|t| t.org == repo.organization) {
             users.push(format!("@{}/{}", gh_team.org, gh_team.name));
         }
-    } else {
+    } else if let Some(team) = &team {
         for member in &team.members {
-            users.push(format!("@{}", member.github));
+            if !opted_out.contains(&(member.github_id as i64)) {
+                users.push(format!("@{}", member.github));
+            }
+        }
+    }
+
+    for subscriber in pings::subscribers(&ctx.db, gh_team).await? {
+        let mention = format!("@{}", subscriber);
+        if !users.contains(&mention) {
+            users.push(mention);
         }
     }
 
@@ -100,11 +197,72 @@ pub(super) async fn handle_command(
         format!("cc {}", users.join(" "))
     };
     let comment = format!("{}\n\n{}", config.message, ping_msg);
-    event
-        .issue()
-        .expect("issue")
-        .post_comment(&ctx.github, &comment)
-        .await?;
+    issue.post_comment(&ctx.github, &comment).await?;
+    pings::record_team_ping(&ctx.db, &repo, issue.number, gh_team).await?;
+
+    if let Some(stream) = config.zulip_stream {
+        let content = format!(
+            "`{}` was pinged on [{} #{}]({}).",
+            gh_team,
+            issue.title,
+            issue.number,
+            event.html_url().unwrap_or(&issue.html_url),
+        );
+        let topic = issue.zulip_topic_reference();
+        let req = MessageApiRequest {
+            recipient: Recipient::Stream {
+                id: stream,
+                topic: &topic,
+            },
+            content: &content,
+        };
+        if let Err(e) = req.send(&ctx.zulip).await {
+            log::error!("failed to notify Zulip of ping to {}: {:?}", gh_team, e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_subscription(
+    ctx: &Context,
+    config: &PingConfig,
+    event: &Event,
+    cmd: PingCommand,
+) -> anyhow::Result<()> {
+    let (gh_team, _) = match config.get_by_name(&cmd.team) {
+        Some(v) => v,
+        None => {
+            let cmnt = ErrorComment::new(
+                &event.issue().unwrap(),
+                format!(
+                    "This team (`{}`) cannot be pinged via this command; \
+                    it may need to be added to `triagebot.toml` on the master branch.",
+                    cmd.team,
+                ),
+            );
+            cmnt.post(&ctx.github).await?;
+            return Ok(());
+        }
+    };
+    let user = event.user();
+    let user_id = match user.id {
+        Some(id) => id,
+        None => anyhow::bail!("missing id of user {}", user.login),
+    };
+
+    match cmd.action {
+        PingAction::Subscribe => {
+            pings::opt_in(&ctx.db, user_id, gh_team).await?;
+            pings::subscribe(&ctx.db, user_id, &user.login, gh_team).await?;
+        }
+        PingAction::Unsubscribe => {
+            // Members of the group have to opt out instead.
+            pings::unsubscribe(&ctx.db, user_id, gh_team).await?;
+            pings::opt_out(&ctx.db, user_id, gh_team).await?;
+        }
+        PingAction::Ping => unreachable!(),
+    }
 
     Ok(())
 }
@@ -1,26 +1,237 @@
+//! Purpose: Run the prioritization workflow of issues.
+//!
+//! `@rustbot prioritize` adds the configured label (e.g. `I-prioritize`) and, if a chat channel is
+//! configured, announces the request there (in a topic of its own on Zulip) so that the issue can
+//! be discussed. `@rustbot prioritize P-high`
+//! sets the priority of the issue, replacing any other priority label.
+//!
+//! Once a priority label lands, however it was added, the prioritization label is removed and the
+//! time it took to prioritize the issue is recorded; the times of a repository are served as JSON
+//! at `/prioritizations?repo=<owner>/<repo>`.
+
 use crate::{
+    chat,
     config::PrioritizeConfig,
-    github::{self, Event},
+    db::prioritizations,
+    github::{self, Event, Issue, IssuesAction, IssuesEvent, LabelChangeOrigin},
     handlers::Context,
+    interactions::ErrorComment,
+    zulip,
 };
 use parser::command::prioritize::PrioritizeCommand;
 
+/// The maximum length of the excerpt of the issue shown in the Zulip topic.
+const SUMMARY_LEN: usize = 500;
+
 pub(super) async fn handle_command(
     ctx: &Context,
     config: &PrioritizeConfig,
     event: &Event,
-    _: PrioritizeCommand,
+    cmd: PrioritizeCommand,
 ) -> anyhow::Result<()> {
+    match cmd.priority {
+        Some(priority) if priority.starts_with(&config.priority_prefix) => {
+            set_priority(ctx, config, event, &priority).await
+        }
+        // Any other word is part of the sentence, e.g. `@rustbot prioritize this-one please`.
+        _ => request(ctx, config, event).await,
+    }
+}
+
+async fn request(ctx: &Context, config: &PrioritizeConfig, event: &Event) -> anyhow::Result<()> {
     let issue = event.issue().unwrap();
     let mut labels = issue.labels().to_owned();
 
     // Don't add the label if it's already there
-    if !labels.iter().any(|l| l.name == config.label) {
+    let requested = labels.iter().any(|l| l.name == config.label);
+    if !requested {
         labels.push(github::Label {
             name: config.label.to_owned(),
         });
     }
 
-    issue.set_labels(&ctx.github, labels).await?;
+    issue
+        .set_labels(
+            &ctx.github,
+            &ctx.db,
+            LabelChangeOrigin::from_event("prioritize", event),
+            labels,
+        )
+        .await?;
+    prioritizations::record_request(
+        &ctx.db,
+        &issue.repository().to_string(),
+        issue.number,
+        &event.user().login,
+    )
+    .await?;
+
+    // The topic was opened when the label was first added.
+    if requested {
+        return Ok(());
+    }
+    if let Some(channel) = config.channel() {
+        let content = format!(
+            "[{} #{}]({}) was requested to be prioritized by @{}.\n\n{}",
+            issue.title,
+            issue.number,
+            issue.html_url,
+            event.user().login,
+            summary(issue),
+        );
+        let topic = issue.zulip_topic();
+        let res = match chat::backend(&channel, ctx) {
+            Ok(backend) => backend.send(Some(&topic), &content).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            log::error!(
+                "failed to open prioritization topic for {}: {:?}",
+                issue.global_id(),
+                e
+            );
+        } else if let Some(stream) = channel.zulip_stream() {
+            zulip::link_topic(ctx, issue, stream).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Quotes the first paragraph of the issue description, shortened to `SUMMARY_LEN` characters.
+fn summary(issue: &Issue) -> String {
+    let paragraph = issue.body.trim().split("\n\n").next().unwrap_or("").trim();
+    if paragraph.is_empty() {
+        return String::from("*No description provided.*");
+    }
+    let mut summary = match paragraph.char_indices().nth(SUMMARY_LEN) {
+        Some((len, _)) => format!("{}…", &paragraph[..len]),
+        None => paragraph.to_owned(),
+    };
+    summary = summary.replace('\n', "\n> ");
+    format!("> {}", summary)
+}
+
+async fn set_priority(
+    ctx: &Context,
+    config: &PrioritizeConfig,
+    event: &Event,
+    priority: &str,
+) -> anyhow::Result<()> {
+    let issue = event.issue().unwrap();
+    let is_team_member = if let Err(_) | Ok(false) = event.user().is_team_member(&ctx.github).await
+    {
+        false
+    } else {
+        true
+    };
+    if !is_team_member {
+        let cmnt = ErrorComment::new(
+            issue,
+            "Setting the priority of issues is restricted to members of the Rust teams.",
+        );
+        cmnt.post(&ctx.github).await?;
+        return Ok(());
+    }
+    let mut labels = issue
+        .labels()
+        .iter()
+        .filter(|l| l.name != config.label && !l.name.starts_with(&config.priority_prefix))
+        .cloned()
+        .collect::<Vec<_>>();
+    labels.push(github::Label {
+        name: priority.to_owned(),
+    });
+    issue
+        .set_labels(
+            &ctx.github,
+            &ctx.db,
+            LabelChangeOrigin::from_event("prioritize", event),
+            labels,
+        )
+        .await?;
+    record_prioritized(ctx, issue, priority).await
+}
+
+async fn record_prioritized(ctx: &Context, issue: &Issue, priority: &str) -> anyhow::Result<()> {
+    let repo = issue.repository().to_string();
+    if let Some(duration) =
+        prioritizations::record_prioritized(&ctx.db, &repo, issue.number, priority).await?
+    {
+        log::info!(
+            "{} was prioritized as {} after {} hours",
+            issue.global_id(),
+            priority,
+            duration.num_hours()
+        );
+    }
     Ok(())
 }
+
+pub(super) enum PrioritizeInput {
+    /// The prioritization label was added.
+    Requested,
+    /// A priority label was added.
+    Prioritized(String),
+}
+
+pub(super) fn parse_input(
+    _ctx: &Context,
+    event: &IssuesEvent,
+    config: Option<&PrioritizeConfig>,
+) -> Result<Option<PrioritizeInput>, String> {
+    if event.action != IssuesAction::Labeled {
+        return Ok(None);
+    }
+    let config = match config {
+        Some(config) => config,
+        None => return Ok(None),
+    };
+    let label = &event.label.as_ref().expect("label").name;
+    if *label == config.label {
+        Ok(Some(PrioritizeInput::Requested))
+    } else if label.starts_with(&config.priority_prefix) {
+        Ok(Some(PrioritizeInput::Prioritized(label.clone())))
+    } else {
+        Ok(None)
+    }
+}
+
+pub(super) async fn handle_input(
+    ctx: &Context,
+    config: &PrioritizeConfig,
+    event: &IssuesEvent,
+    input: PrioritizeInput,
+) -> anyhow::Result<()> {
+    let issue = &event.issue;
+    let priority = match input {
+        PrioritizeInput::Requested => {
+            // Requests made through the command were recorded already, and are kept as is.
+            return prioritizations::record_request(
+                &ctx.db,
+                &issue.repository().to_string(),
+                issue.number,
+                &event.sender.login,
+            )
+            .await;
+        }
+        PrioritizeInput::Prioritized(priority) => priority,
+    };
+
+    if issue.labels().iter().any(|l| l.name == config.label) {
+        let labels = issue
+            .labels()
+            .iter()
+            .filter(|l| l.name != config.label)
+            .cloned()
+            .collect();
+        issue
+            .set_labels(
+                &ctx.github,
+                &ctx.db,
+                LabelChangeOrigin::from_issues_event("prioritize", event),
+                labels,
+            )
+            .await?;
+    }
+    record_prioritized(ctx, issue, &priority).await
+}
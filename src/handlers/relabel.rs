@@ -1,7 +1,17 @@
 //! Purpose: Allow any user to modify issue labels on GitHub via comments.
 //!
-//! Labels are checked against the labels in the project; the bot does not support creating new
-//! labels.
+//! Labels are checked against the labels in the project; labels which do not exist yet are only
+//! created if they are declared in the `[labels]` section of `triagebot.toml`.
+//!
+//! Who may change which labels is configured in the `[relabel]` section of `triagebot.toml`:
+//! `[[relabel.rule]]` entries restrict label patterns to members of specific teams, `author-only`
+//! patterns may only be toggled by the issue author. All other labels may be changed by Rust team
+//! members, or by anyone if they match `allow-unauthenticated`. Commands adding two labels of the
+//! same `[exclusive-labels]` group are rejected.
+//!
+//! Issues in other repositories can be relabeled by referencing them in the command, e.g.
+//! `@rustbot label rust-lang/cargo#123 +T-cargo`; the `[relabel]` configuration of the referenced
+//! repository applies, and it must have relabeling enabled.
 //!
 //! Parsing is done in the `parser::command::relabel` module.
 //!
@@ -9,12 +19,12 @@
 //! notification noise.
 
 use crate::{
-    config::RelabelConfig,
-    github::{self, Event, GithubClient},
-    handlers::Context,
+    config::{ExclusiveLabelsConfig, RelabelConfig},
+    github::{self, Event, GithubClient, Issue, LabelChangeOrigin, Repository},
+    handlers::{Context, HandlerError},
     interactions::ErrorComment,
 };
-use parser::command::relabel::{LabelDelta, RelabelCommand};
+use parser::command::relabel::{IssueTarget, LabelDelta, RelabelCommand};
 
 pub(super) async fn handle_command(
     ctx: &Context,
@@ -22,27 +32,193 @@ pub(super) async fn handle_command(
     event: &Event,
     input: RelabelCommand,
 ) -> anyhow::Result<()> {
-    let mut issue_labels = event.issue().unwrap().labels().to_owned();
+    let issue = event.issue().unwrap();
+
+    let membership = is_member(&event.user(), &ctx.github).await;
+    // Rules of other repositories may need the user's teams as well.
+    let teams = if config.rules.is_empty() && input.targets.is_empty() {
+        Some(Vec::new())
+    } else {
+        match event.user().teams(&ctx.github).await {
+            Ok(teams) => Some(teams),
+            Err(err) => {
+                log::error!("failed to fetch teams of {}: {:?}", event.user().login, err);
+                None
+            }
+        }
+    };
+    let actor = Actor {
+        login: &event.user().login,
+        membership,
+        teams,
+        origin: LabelChangeOrigin::from_event("relabel", event),
+    };
+
+    if input.targets.is_empty() {
+        let repo_config = crate::config::get(&ctx.github, event.repo_name())
+            .await
+            .ok();
+        let exclusive = repo_config
+            .as_ref()
+            .and_then(|c| c.exclusive_labels.as_ref());
+        let denial = relabel_issue(ctx, config, exclusive, issue, &input.deltas, &actor).await?;
+        if let Some(msg) = denial {
+            let cmnt = ErrorComment::new(&issue, msg);
+            cmnt.post(&ctx.github).await?;
+        }
+        return Ok(());
+    }
+
+    let current = issue.repository();
+    let mut denials = Vec::new();
+    for target in &input.targets {
+        let repo = Repository {
+            full_name: format!(
+                "{}/{}",
+                target
+                    .organization
+                    .as_deref()
+                    .unwrap_or(&current.organization),
+                target.repository.as_deref().unwrap_or(&current.repository)
+            ),
+        };
+        // A failure on one target doesn't stop the others, and is reported with the denials.
+        match relabel_target(ctx, &repo, target, &input.deltas, &actor).await {
+            Ok(None) => {}
+            Ok(Some(msg)) => denials.push(format!("{}: {}", target, msg)),
+            // Only errors caused by the user's input are shown to them.
+            Err(e) => match HandlerError::from(e) {
+                HandlerError::Message(msg) => denials.push(format!("{}: {}", target, msg)),
+                HandlerError::Other(err) => {
+                    log::error!("failed to relabel {}: {:?}", target, err);
+                    denials.push(format!("{}: An internal error occurred.", target));
+                }
+            },
+        }
+    }
+    if !denials.is_empty() {
+        let cmnt = ErrorComment::new(&issue, denials.join("\n"));
+        cmnt.post(&ctx.github).await?;
+    }
+
+    Ok(())
+}
+
+/// The user issuing the relabel command.
+struct Actor<'a> {
+    login: &'a str,
+    membership: TeamMembership,
+    /// The teams the user is a member of, or `None` if they could not be determined.
+    teams: Option<Vec<String>>,
+    origin: LabelChangeOrigin<'a>,
+}
+
+/// Relabels an issue referenced from another issue, following the configuration of the repository
+/// the referenced issue lives in.
+///
+/// Returns the reason the labels were not changed, if any.
+async fn relabel_target(
+    ctx: &Context,
+    repo: &Repository,
+    target: &IssueTarget,
+    deltas: &[LabelDelta],
+    actor: &Actor<'_>,
+) -> anyhow::Result<Option<String>> {
+    let repo_config = match crate::config::get(&ctx.github, &repo.full_name).await {
+        Ok(repo_config) => repo_config,
+        Err(e) => {
+            log::warn!("failed to get configuration of {}: {}", repo.full_name, e);
+            return Ok(Some(format!(
+                "Relabeling is not enabled in `{}`.",
+                repo.full_name
+            )));
+        }
+    };
+    let config = match &repo_config.relabel {
+        Some(config) => config,
+        None => {
+            return Ok(Some(format!(
+                "Relabeling is not enabled in `{}`.",
+                repo.full_name
+            )))
+        }
+    };
+    let issue = match repo.get_issue(&ctx.github, target.number).await {
+        Ok(issue) => issue,
+        Err(e) => {
+            log::warn!("failed to get {}: {:?}", target, e);
+            return Ok(Some(format!(
+                "Issue {}#{} could not be found.",
+                repo.full_name, target.number
+            )));
+        }
+    };
+    relabel_issue(
+        ctx,
+        config,
+        repo_config.exclusive_labels.as_ref(),
+        &issue,
+        deltas,
+        actor,
+    )
+    .await
+}
+
+/// Applies `deltas` to `issue` if the actor is allowed to make all of the changes.
+///
+/// Returns the reason the labels were not changed, if any.
+async fn relabel_issue(
+    ctx: &Context,
+    config: &RelabelConfig,
+    exclusive: Option<&ExclusiveLabelsConfig>,
+    issue: &Issue,
+    deltas: &[LabelDelta],
+    actor: &Actor<'_>,
+) -> anyhow::Result<Option<String>> {
+    let mut issue_labels = issue.labels().to_owned();
     let mut changed = false;
-    for delta in &input.0 {
+
+    let user = UserInfo {
+        teams: actor.teams.clone(),
+        is_author: issue.user.login == actor.login,
+    };
+
+    let added = deltas
+        .iter()
+        .filter_map(|delta| match delta {
+            LabelDelta::Add(label) => Some(label.as_str()),
+            LabelDelta::Remove(_) => None,
+        })
+        .collect::<Vec<_>>();
+    if let Some(exclusive) = exclusive {
+        if let Err(msg) = check_exclusive(&added, exclusive) {
+            return Ok(Some(msg));
+        }
+    }
+
+    for delta in deltas {
         let name = delta.label().as_str();
-        let err = match check_filter(name, config, is_member(&event.user(), &ctx.github).await) {
-            Ok(CheckFilterResult::Allow) => None,
-            Ok(CheckFilterResult::Deny) => Some(format!(
-                "Label {} can only be set by Rust team members",
-                name
-            )),
-            Ok(CheckFilterResult::DenyUnknown) => Some(format!(
-                "Label {} can only be set by Rust team members;\
-                 we were unable to check if you are a team member.",
-                name
-            )),
+        let adding = matches!(delta, LabelDelta::Add(_));
+        let err = match check_rules(name, adding, config, &user) {
+            Ok(CheckRuleResult::Allow) => None,
+            Ok(CheckRuleResult::Deny(msg)) => Some(msg),
+            Ok(CheckRuleResult::NoMatch) => match check_filter(name, config, actor.membership) {
+                Ok(CheckFilterResult::Allow) => None,
+                Ok(CheckFilterResult::Deny) => Some(format!(
+                    "Label {} can only be set by Rust team members",
+                    name
+                )),
+                Ok(CheckFilterResult::DenyUnknown) => Some(format!(
+                    "Label {} can only be set by Rust team members;\
+                     we were unable to check if you are a team member.",
+                    name
+                )),
+                Err(err) => Some(err),
+            },
             Err(err) => Some(err),
         };
-        if let Some(msg) = err {
-            let cmnt = ErrorComment::new(&event.issue().unwrap(), msg);
-            cmnt.post(&ctx.github).await?;
-            return Ok(());
+        if err.is_some() {
+            return Ok(err);
         }
         match delta {
             LabelDelta::Add(label) => {
@@ -63,17 +239,15 @@ pub(super) async fn handle_command(
     }
 
     if changed {
-        event
-            .issue()
-            .unwrap()
-            .set_labels(&ctx.github, issue_labels)
+        issue
+            .set_labels(&ctx.github, &ctx.db, actor.origin, issue_labels)
             .await?;
     }
 
-    Ok(())
+    Ok(None)
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 enum TeamMembership {
     Member,
     Outsider,
@@ -131,6 +305,119 @@ fn check_filter(
     }
 }
 
+struct UserInfo {
+    /// The teams the user is a member of, or `None` if they could not be determined.
+    teams: Option<Vec<String>>,
+    is_author: bool,
+}
+
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+enum CheckRuleResult {
+    Allow,
+    Deny(String),
+    /// No rule applies to this label; fall back to `check_filter`.
+    NoMatch,
+}
+
+fn matches_any(patterns: &[String], label: &str) -> Result<bool, String> {
+    for pattern in patterns {
+        match glob::Pattern::new(pattern) {
+            Ok(glob) if glob.matches(label) => return Ok(true),
+            Ok(_) => {}
+            Err(err) => {
+                log::error!("failed to match pattern {}: {}", pattern, err);
+                return Err(format!("failed to match pattern {}", pattern));
+            }
+        }
+    }
+    Ok(false)
+}
+
+fn check_rules(
+    label: &str,
+    adding: bool,
+    config: &RelabelConfig,
+    user: &UserInfo,
+) -> Result<CheckRuleResult, String> {
+    let author_only = matches_any(&config.author_only, label)?;
+    if author_only && user.is_author {
+        return Ok(CheckRuleResult::Allow);
+    }
+
+    let verb = if adding { "added" } else { "removed" };
+    let mut allowed_teams = Vec::new();
+    for rule in &config.rules {
+        if !matches_any(&rule.labels, label)? {
+            continue;
+        }
+        let teams = if adding { &rule.add } else { &rule.remove };
+        allowed_teams.extend(teams.iter().map(|t| t.as_str()));
+    }
+
+    if !allowed_teams.is_empty() {
+        let user_teams = match &user.teams {
+            Some(teams) => teams,
+            None => {
+                return Ok(CheckRuleResult::Deny(format!(
+                    "Label `{}` can only be {} by members of specific teams; \
+                     we were unable to check which teams you are a member of.",
+                    label, verb
+                )))
+            }
+        };
+        if allowed_teams
+            .iter()
+            .any(|team| user_teams.iter().any(|t| t == team))
+        {
+            return Ok(CheckRuleResult::Allow);
+        }
+        allowed_teams.sort_unstable();
+        allowed_teams.dedup();
+        let teams = allowed_teams
+            .iter()
+            .map(|t| format!("`{}`", t))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let also_author = if author_only {
+            " and by the issue author"
+        } else {
+            ""
+        };
+        return Ok(CheckRuleResult::Deny(format!(
+            "Label `{}` can only be {} by members of the following teams: {}{}.",
+            label, verb, teams, also_author
+        )));
+    }
+
+    if author_only {
+        return Ok(CheckRuleResult::Deny(format!(
+            "Label `{}` can only be {} by the author of this issue.",
+            label, verb
+        )));
+    }
+
+    Ok(CheckRuleResult::NoMatch)
+}
+
+/// Rejects commands which add more than one label of the same exclusive group.
+fn check_exclusive(added: &[&str], config: &ExclusiveLabelsConfig) -> Result<(), String> {
+    for (idx, label) in added.iter().enumerate() {
+        for group in config.groups_of(label) {
+            if let Some(other) = added[idx + 1..]
+                .iter()
+                .find(|other| *other != label && config.groups_of(other).contains(&group))
+            {
+                return Err(format!(
+                    "Labels `{}` and `{}` cannot both be applied: \
+                     labels of the `{}` group are mutually exclusive.",
+                    label, other, group
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
 #[cfg_attr(test, derive(Debug, PartialEq, Eq))]
 enum MatchPatternResult {
     Allow,
@@ -155,9 +442,10 @@ fn match_pattern(pattern: &str, label: &str) -> anyhow::Result<MatchPatternResul
 #[cfg(test)]
 mod tests {
     use super::{
-        check_filter, match_pattern, CheckFilterResult, MatchPatternResult, TeamMembership,
+        check_exclusive, check_filter, check_rules, match_pattern, CheckFilterResult,
+        CheckRuleResult, MatchPatternResult, TeamMembership, UserInfo,
     };
-    use crate::config::RelabelConfig;
+    use crate::config::{ExclusiveLabelsConfig, RelabelConfig, RelabelRuleConfig};
 
     #[test]
     fn test_match_pattern() -> anyhow::Result<()> {
@@ -186,6 +474,8 @@ mod tests {
             ($($member:ident { $($label:expr => $res:ident,)* })*) => {
                 let config = RelabelConfig {
                     allow_unauthenticated: vec!["T-*".into(), "I-*".into(), "!I-nominated".into()],
+                    author_only: vec![],
+                    rules: vec![],
                 };
                 $($(assert_eq!(
                     check_filter($label, &config, TeamMembership::$member),
@@ -215,4 +505,86 @@ mod tests {
         }
         Ok(())
     }
+
+    fn rules_config() -> RelabelConfig {
+        RelabelConfig {
+            allow_unauthenticated: vec![],
+            author_only: vec!["S-waiting-on-*".into()],
+            rules: vec![
+                RelabelRuleConfig {
+                    labels: vec!["beta-accepted".into()],
+                    add: vec!["release".into()],
+                    remove: vec!["release".into()],
+                },
+                RelabelRuleConfig {
+                    labels: vec!["S-waiting-on-*".into()],
+                    add: vec!["compiler".into()],
+                    remove: vec![],
+                },
+            ],
+        }
+    }
+
+    fn user(teams: Option<&[&str]>, is_author: bool) -> UserInfo {
+        UserInfo {
+            teams: teams.map(|t| t.iter().map(|t| t.to_string()).collect()),
+            is_author,
+        }
+    }
+
+    #[test]
+    fn test_check_rules() {
+        let config = rules_config();
+        let add = |l| (l, true);
+        let remove = |l| (l, false);
+        let check = |(label, adding), user| check_rules(label, adding, &config, &user);
+
+        // Team rules.
+        assert_eq!(
+            check(add("beta-accepted"), user(Some(&["release"]), false)),
+            Ok(CheckRuleResult::Allow)
+        );
+        assert!(matches!(
+            check(add("beta-accepted"), user(Some(&["lang"]), false)),
+            Ok(CheckRuleResult::Deny(_))
+        ));
+        assert!(matches!(
+            check(add("beta-accepted"), user(None, false)),
+            Ok(CheckRuleResult::Deny(_))
+        ));
+
+        // Author-only labels, with a team rule for adding only.
+        assert_eq!(
+            check(add("S-waiting-on-review"), user(Some(&[]), true)),
+            Ok(CheckRuleResult::Allow)
+        );
+        assert_eq!(
+            check(add("S-waiting-on-review"), user(Some(&["compiler"]), false)),
+            Ok(CheckRuleResult::Allow)
+        );
+        assert!(matches!(
+            check(
+                remove("S-waiting-on-review"),
+                user(Some(&["compiler"]), false)
+            ),
+            Ok(CheckRuleResult::Deny(_))
+        ));
+
+        // Labels without rules fall back to `check_filter`.
+        assert_eq!(
+            check(add("T-lang"), user(Some(&[]), false)),
+            Ok(CheckRuleResult::NoMatch)
+        );
+    }
+
+    #[test]
+    fn test_check_exclusive() {
+        let mut groups = std::collections::HashMap::new();
+        groups.insert("priority".to_string(), vec!["P-*".to_string()]);
+        let config = ExclusiveLabelsConfig { groups };
+        assert!(check_exclusive(&["P-high"], &config).is_ok());
+        assert!(check_exclusive(&["P-high", "T-lang"], &config).is_ok());
+        assert!(check_exclusive(&["P-high", "P-high"], &config).is_ok());
+        assert!(check_exclusive(&["P-high", "P-low"], &config).is_err());
+    }
 }
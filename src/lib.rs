@@ -10,7 +10,9 @@ use std::fmt;
 
 pub mod actions;
 pub mod agenda;
+pub mod backport_status;
 mod changelogs;
+mod chat;
 pub mod config;
 pub mod db;
 pub mod github;
@@ -91,11 +93,21 @@ pub fn deserialize_payload<T: serde::de::DeserializeOwned>(v: &str) -> anyhow::R
     }
 }
 
+/// Escapes `s` for use in the HTML pages served by the bot.
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
 pub async fn webhook(
     event: EventName,
     payload: String,
     ctx: &handlers::Context,
 ) -> Result<bool, WebhookError> {
+    let mut events = Vec::new();
     let event = match event {
         EventName::PullRequestReview => {
             let payload = deserialize_payload::<github::PullRequestReviewEvent>(&payload)
@@ -104,6 +116,21 @@ pub async fn webhook(
 
             log::info!("handling pull request review comment {:?}", payload);
 
+            // Submitted reviews also drive the state of the pull request.
+            if payload.action == github::PullRequestReviewAction::Submitted {
+                let mut issue = payload.pull_request.clone();
+                issue.pull_request = Some(github::PullRequestDetails {});
+                events.push(github::Event::Issue(github::IssuesEvent {
+                    action: github::IssuesAction::ReviewSubmitted,
+                    issue,
+                    changes: None,
+                    repository: payload.repository.clone(),
+                    label: None,
+                    sender: payload.review.user.clone(),
+                    review_state: payload.review.state.clone(),
+                }));
+            }
+
             // Treat pull request review comments exactly like pull request
             // review comments.
             github::Event::IssueComment(github::IssueCommentEvent {
@@ -120,6 +147,7 @@ pub async fn webhook(
                 issue: payload.pull_request,
                 comment: payload.review,
                 repository: payload.repository,
+                sender: payload.sender,
             })
         }
         EventName::PullRequestReviewComment => {
@@ -137,6 +165,7 @@ pub async fn webhook(
                 issue: payload.issue,
                 comment: payload.comment,
                 repository: payload.repository,
+                sender: payload.sender,
             })
         }
         EventName::IssueComment => {
@@ -149,9 +178,13 @@ pub async fn webhook(
             github::Event::IssueComment(payload)
         }
         EventName::Issue | EventName::PullRequest => {
-            let payload = deserialize_payload::<github::IssuesEvent>(&payload)
+            let mut payload = deserialize_payload::<github::IssuesEvent>(&payload)
                 .context(format!("{:?} failed to deserialize", event))
                 .map_err(anyhow::Error::from)?;
+            // Pull requests don't point to themselves in their payloads.
+            if let EventName::PullRequest = event {
+                payload.issue.pull_request = Some(github::PullRequestDetails {});
+            }
 
             log::info!("handling issue event {:?}", payload);
 
@@ -180,27 +213,30 @@ pub async fn webhook(
             return Ok(false);
         }
     };
-    let errors = handlers::handle(&ctx, &event).await;
+    events.push(event);
     let mut other_error = false;
-    let mut message = String::new();
-    for err in errors {
-        match err {
-            HandlerError::Message(msg) => {
-                if !message.is_empty() {
-                    message.push_str("\n\n");
+    for event in &events {
+        let errors = handlers::handle(&ctx, event).await;
+        let mut message = String::new();
+        for err in errors {
+            match err {
+                HandlerError::Message(msg) => {
+                    if !message.is_empty() {
+                        message.push_str("\n\n");
+                    }
+                    message.push_str(&msg);
+                }
+                HandlerError::Other(err) => {
+                    log::error!("handling event failed: {:?}", err);
+                    other_error = true;
                 }
-                message.push_str(&msg);
-            }
-            HandlerError::Other(err) => {
-                log::error!("handling event failed: {:?}", err);
-                other_error = true;
             }
         }
-    }
-    if !message.is_empty() {
-        if let Some(issue) = event.issue() {
-            let cmnt = ErrorComment::new(issue, message);
-            cmnt.post(&ctx.github).await?;
+        if !message.is_empty() {
+            if let Some(issue) = event.issue() {
+                let cmnt = ErrorComment::new(issue, message);
+                cmnt.post(&ctx.github).await?;
+            }
         }
     }
     if other_error {
@@ -4,10 +4,19 @@ use anyhow::Context as _;
 use futures::{future::FutureExt, stream::StreamExt};
 use hyper::{header, Body, Request, Response, Server, StatusCode};
 use reqwest::Client;
-use std::{env, net::SocketAddr, sync::Arc};
-use triagebot::{db, github, handlers::Context, logger, notification_listing, payload, EventName};
+use std::{env, net::SocketAddr, sync::Arc, time::Duration};
+use triagebot::{
+    backport_status, db, github,
+    handlers::{self, Context},
+    logger, notification_listing, payload, EventName,
+};
 use uuid::Uuid;
 
+/// How often the scheduled handlers run.
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How often due reminders are sent; reminders are set to the minute.
+const REMINDER_INTERVAL: Duration = Duration::from_secs(60);
+
 async fn serve_req(req: Request<Body>, ctx: Arc<Context>) -> Result<Response<Body>, hyper::Error> {
     log::info!("request = {:?}", req);
     let (req, body_stream) = req.into_parts();
@@ -54,6 +63,95 @@ async fn serve_req(req: Request<Body>, ctx: Arc<Context>) -> Result<Response<Bod
             )))
             .unwrap());
     }
+    if req.uri.path() == "/backports" {
+        if let Some(query) = req.uri.query() {
+            let repo = url::form_urlencoded::parse(query.as_bytes()).find(|(k, _)| k == "repo");
+            if let Some((_, repo)) = repo {
+                return Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .body(Body::from(backport_status::render(&ctx.db, &*repo).await))
+                    .unwrap());
+            }
+        }
+
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::from(String::from(
+                "Please provide `?repo=<owner>/<repo>` query param on URL.",
+            )))
+            .unwrap());
+    }
+    if req.uri.path() == "/labels/history" {
+        let mut repo = None;
+        let mut issue = None;
+        if let Some(query) = req.uri.query() {
+            for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+                match &*key {
+                    "repo" => repo = Some(value.into_owned()),
+                    "issue" => issue = value.parse::<u64>().ok(),
+                    _ => {}
+                }
+            }
+        }
+        let (repo, issue) = match (repo, issue) {
+            (Some(repo), Some(issue)) => (repo, issue),
+            _ => {
+                return Ok(Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(Body::from(
+                        "Please provide `?repo=<owner>/<repo>&issue=<number>` query params on URL.",
+                    ))
+                    .unwrap());
+            }
+        };
+        let res = db::label_changes::get_label_changes(&ctx.db, &repo, issue).await;
+        let res = match res {
+            Ok(r) => r,
+            Err(e) => {
+                return Ok(Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::from(format!("{:?}", e)))
+                    .unwrap());
+            }
+        };
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_string(&res).unwrap()))
+            .unwrap());
+    }
+    if req.uri.path() == "/prioritizations" {
+        let repo = req.uri.query().and_then(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .find(|(k, _)| k == "repo")
+                .map(|(_, repo)| repo.into_owned())
+        });
+        let repo = match repo {
+            Some(repo) => repo,
+            None => {
+                return Ok(Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(Body::from(
+                        "Please provide `?repo=<owner>/<repo>` query param on URL.",
+                    ))
+                    .unwrap());
+            }
+        };
+        let res = match db::prioritizations::get_prioritizations(&ctx.db, &repo).await {
+            Ok(r) => r,
+            Err(e) => {
+                return Ok(Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::from(format!("{:?}", e)))
+                    .unwrap());
+            }
+        };
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_string(&res).unwrap()))
+            .unwrap());
+    }
     if req.uri.path() == "/zulip-hook" {
         let mut c = body_stream;
         let mut payload = Vec::new();
@@ -184,11 +282,32 @@ async fn run_server(addr: SocketAddr) -> anyhow::Result<()> {
         .personal_token(github::default_token_from_env())
         .build()
         .expect("Failed to build octograb.");
+    let zulip =
+        triagebot::zulip::ZulipClient::from_env(client.clone()).context("Zulip configuration")?;
     let ctx = Arc::new(Context {
         username: String::from("rustbot"),
         db: db_client,
         github: gh,
         octocrab: oc,
+        zulip,
+    });
+
+    let scheduler_ctx = ctx.clone();
+    tokio::spawn(async move {
+        loop {
+            handlers::handle_scheduled(&scheduler_ctx).await;
+            tokio::time::delay_for(SCHEDULER_INTERVAL).await;
+        }
+    });
+
+    let reminder_ctx = ctx.clone();
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = triagebot::zulip::send_reminders(&reminder_ctx).await {
+                log::error!("failed to send notification reminders: {:?}", e);
+            }
+            tokio::time::delay_for(REMINDER_INTERVAL).await;
+        }
     });
 
     let svc = hyper::service::make_service_fn(move |_conn| {
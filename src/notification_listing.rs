@@ -1,5 +1,7 @@
-use crate::db::notifications::get_notifications;
+use crate::db::notifications::{get_notifications, NotificationData};
 use crate::db::DbClient;
+use crate::escape_html;
+use chrono::{DateTime, Utc};
 
 pub async fn render(db: &DbClient, user: &str) -> String {
     let notifications = match get_notifications(db, user).await {
@@ -19,34 +21,32 @@ pub async fn render(db: &DbClient, user: &str) -> String {
 
     out.push_str(&format!("<h3>Pending notifications for {}</h3>", user));
 
-    if notifications.is_empty() {
+    let now = Utc::now();
+    let snoozed = notifications.iter().filter(|n| n.is_snoozed(now)).count();
+
+    if notifications.len() == snoozed {
         out.push_str("<p><em>You have no pending notifications! :)</em></p>");
     } else {
         out.push_str("<ol>");
-        for notification in notifications {
-            out.push_str("<li>");
+        for (idx, notification) in listed(&notifications, now) {
+            out.push_str(&format!("<li value='{}'>", idx));
             out.push_str(&format!(
                 "<a href='{}'>{}</a>",
                 notification.origin_url,
-                notification
-                    .short_description
-                    .as_ref()
-                    .unwrap_or(&notification.origin_url)
-                    .replace('&', "&amp;")
-                    .replace('<', "&lt;")
-                    .replace('>', "&gt;")
-                    .replace('"', "&quot;")
-                    .replace('\'', "&#39;"),
+                escape_html(
+                    notification
+                        .short_description
+                        .as_ref()
+                        .unwrap_or(&notification.origin_url)
+                ),
             ));
             if let Some(metadata) = &notification.metadata {
+                out.push_str(&format!("<ul><li>{}</li></ul>", escape_html(metadata)));
+            }
+            if let Some(remind_at) = &notification.remind_at {
                 out.push_str(&format!(
-                    "<ul><li>{}</li></ul>",
-                    metadata
-                        .replace('&', "&amp;")
-                        .replace('<', "&lt;")
-                        .replace('>', "&gt;")
-                        .replace('"', "&quot;")
-                        .replace('\'', "&#39;"),
+                    "<ul><li><em>Reminder on {}</em></li></ul>",
+                    remind_at.format("%Y-%m-%d %H:%M UTC"),
                 ));
             }
             out.push_str("</li>");
@@ -61,8 +61,65 @@ pub async fn render(db: &DbClient, user: &str) -> String {
         );
     }
 
+    if snoozed > 0 {
+        out.push_str(&format!(
+            "<p><em>{} snoozed notification{} hidden.</em></p>",
+            snoozed,
+            if snoozed == 1 { " is" } else { "s are" },
+        ));
+    }
+
     out.push_str("</body>");
     out.push_str("</html>");
 
     out
 }
+
+/// The notifications to list with their 1-based indexes, which are their positions among all the
+/// notifications of the user. Snoozed notifications are left out but keep their index, so that
+/// the commands refer to the same notifications once they are due.
+fn listed(
+    notifications: &[NotificationData],
+    now: DateTime<Utc>,
+) -> impl Iterator<Item = (usize, &NotificationData)> {
+    notifications
+        .iter()
+        .enumerate()
+        .filter(move |(_, n)| !n.is_snoozed(now))
+        .map(|(idx, n)| (idx + 1, n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(url: &str, snoozed_until: Option<&str>) -> NotificationData {
+        let time = |t: &str| DateTime::parse_from_rfc3339(t).unwrap();
+        NotificationData {
+            origin_url: url.to_owned(),
+            origin_text: String::new(),
+            short_description: None,
+            time: time("2020-12-01T00:00:00Z"),
+            metadata: None,
+            snoozed_until: snoozed_until.map(time),
+            remind_at: None,
+        }
+    }
+
+    #[test]
+    fn snoozed_notifications_keep_indexes() {
+        let now = DateTime::parse_from_rfc3339("2020-12-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let notifications = vec![
+            notification("a", None),
+            notification("b", Some("2020-12-02T00:00:00Z")),
+            notification("c", Some("2020-12-01T06:00:00Z")),
+            notification("d", None),
+        ];
+        let listed = listed(&notifications, now)
+            .map(|(idx, n)| (idx, n.origin_url.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(listed, vec![(1, "a"), (3, "c"), (4, "d")]);
+    }
+}
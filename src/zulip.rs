@@ -1,11 +1,14 @@
+use crate::db::label_changes::get_label_changes;
 use crate::db::notifications::add_metadata;
 use crate::db::notifications::{self, delete_ping, move_indices, record_ping, Identifier};
+use crate::db::pings;
+use crate::db::zulip_topics;
 use crate::github::{self, GithubClient};
 use crate::handlers::Context;
 use anyhow::Context as _;
 use std::convert::TryInto;
 use std::env;
-use std::fmt::Write as _;
+use std::fmt::{self, Write as _};
 
 #[derive(Debug, serde::Deserialize)]
 pub struct Request {
@@ -44,6 +47,103 @@ struct ResponseOwned {
 
 pub const BOT_EMAIL: &str = "triage-rust-lang-bot@zulipchat.com";
 
+/// The realm used when `ZULIP_REALM` is not set.
+const DEFAULT_REALM: &str = "https://rust-lang.zulipchat.com";
+
+/// A client of the API of a Zulip realm, configured once at startup.
+#[derive(Clone)]
+pub struct ZulipClient {
+    client: reqwest::Client,
+    /// The URL of the realm, e.g. `https://rust-lang.zulipchat.com`, without a trailing slash.
+    realm: String,
+    bot_email: String,
+    api_token: String,
+    /// The token sent by Zulip with the messages of the outgoing webhook.
+    webhook_token: String,
+}
+
+impl fmt::Debug for ZulipClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Leave the tokens out.
+        f.debug_struct("ZulipClient")
+            .field("realm", &self.realm)
+            .field("bot_email", &self.bot_email)
+            .finish()
+    }
+}
+
+impl ZulipClient {
+    pub fn new(
+        client: reqwest::Client,
+        realm: &str,
+        bot_email: String,
+        api_token: String,
+        webhook_token: String,
+    ) -> anyhow::Result<Self> {
+        let url = url::Url::parse(realm).with_context(|| format!("invalid realm {}", realm))?;
+        if url.scheme() != "https" && url.scheme() != "http" {
+            anyhow::bail!("invalid realm {}: not an HTTP URL", realm);
+        }
+        Ok(ZulipClient {
+            client,
+            realm: realm.trim_end_matches('/').to_owned(),
+            bot_email,
+            api_token,
+            webhook_token,
+        })
+    }
+
+    /// Configures the client from `ZULIP_REALM`, `ZULIP_BOT_EMAIL`, `ZULIP_API_TOKEN` and
+    /// `ZULIP_TOKEN` (the webhook token); the realm and the email have defaults.
+    pub fn from_env(client: reqwest::Client) -> anyhow::Result<Self> {
+        let var = |name: &str| env::var(name).with_context(|| format!("{} is not set", name));
+        ZulipClient::new(
+            client,
+            &env::var("ZULIP_REALM").unwrap_or_else(|_| DEFAULT_REALM.to_owned()),
+            env::var("ZULIP_BOT_EMAIL").unwrap_or_else(|_| BOT_EMAIL.to_owned()),
+            var("ZULIP_API_TOKEN")?,
+            var("ZULIP_TOKEN")?,
+        )
+    }
+
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    /// Sends `content` as a direct message to `recipient`, which must not be a stream.
+    pub async fn direct_message(
+        &self,
+        recipient: Recipient<'_>,
+        content: &str,
+    ) -> anyhow::Result<()> {
+        MessageApiRequest { recipient, content }
+            .send(self)
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Whether `token` is the token of the outgoing webhook.
+    pub fn is_webhook_token(&self, token: &str) -> bool {
+        openssl::memcmp::eq(token.as_bytes(), self.webhook_token.as_bytes())
+    }
+
+    /// A request to the endpoint at `path` of the API, e.g. `messages`, authenticated as the bot.
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .request(method, &format!("{}/api/v1/{}", self.realm, path))
+            .basic_auth(&self.bot_email, Some(&self.api_token))
+    }
+
+    pub(crate) fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.request(reqwest::Method::GET, path)
+    }
+
+    pub(crate) fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.request(reqwest::Method::POST, path)
+    }
+}
+
 pub async fn to_github_id(client: &GithubClient, zulip_id: usize) -> anyhow::Result<Option<i64>> {
     let map = crate::team_data::zulip_map(client).await?;
     Ok(map.users.get(&zulip_id).map(|v| *v as i64))
@@ -59,9 +159,7 @@ pub async fn to_zulip_id(client: &GithubClient, github_id: i64) -> anyhow::Resul
 }
 
 pub async fn respond(ctx: &Context, req: Request) -> String {
-    let expected_token = std::env::var("ZULIP_TOKEN").expect("`ZULIP_TOKEN` set for authorization");
-
-    if !openssl::memcmp::eq(req.token.as_bytes(), expected_token.as_bytes()) {
+    if !ctx.zulip.is_webhook_token(&req.token) {
         return serde_json::to_string(&Response {
             content: "Invalid authorization.",
         })
@@ -149,6 +247,56 @@ fn handle_command<'a>(
                 })
                 .unwrap(),
             },
+            Some("label-history") => match label_history(&ctx, words).await {
+                Ok(r) => r,
+                Err(e) => serde_json::to_string(&Response {
+                    content: &format!(
+                        "Failed to parse label history request, expected `label-history <owner>/<repo>#<number>`: {:?}.",
+                        e
+                    ),
+                })
+                .unwrap(),
+            },
+            Some("ping-opt-out") => match ping_preference(&ctx, gh_id, words, false).await {
+                Ok(r) => r,
+                Err(e) => serde_json::to_string(&Response {
+                    content: &format!(
+                        "Failed to parse opt-out, expected `ping-opt-out <team>`: {:?}.",
+                        e
+                    ),
+                })
+                .unwrap(),
+            },
+            Some("ping-opt-in") => match ping_preference(&ctx, gh_id, words, true).await {
+                Ok(r) => r,
+                Err(e) => serde_json::to_string(&Response {
+                    content: &format!(
+                        "Failed to parse opt-in, expected `ping-opt-in <team>`: {:?}.",
+                        e
+                    ),
+                })
+                .unwrap(),
+            },
+            Some("snooze") => match snooze_notification(gh_id, words).await {
+                Ok(r) => r,
+                Err(e) => serde_json::to_string(&Response {
+                    content: &format!(
+                        "Failed to parse snooze, expected `snooze <idx> <duration>`, e.g. `snooze 1 3d`: {:?}.",
+                        e
+                    ),
+                })
+                .unwrap(),
+            },
+            Some("remind") => match remind_notification(gh_id, words).await {
+                Ok(r) => r,
+                Err(e) => serde_json::to_string(&Response {
+                    content: &format!(
+                        "Failed to parse reminder, expected `remind <idx> <date>`, e.g. `remind 1 2020-12-24`: {:?}.",
+                        e
+                    ),
+                })
+                .unwrap(),
+            },
             Some("meta") => match add_meta_notification(gh_id, words).await {
                 Ok(r) => r,
                 Err(e) => serde_json::to_string(&Response {
@@ -178,6 +326,22 @@ fn handle_command<'a>(
                                 })
                                 .unwrap(),
                             },
+                            Some("summarize-to-github") => return match parse_summary_range(words) {
+                                Ok((first, last)) => match summarize_to_github(&ctx, message_data, first, last).await {
+                                    Ok(r) => r,
+                                    Err(e) => serde_json::to_string(&Response {
+                                        content: &format!("Failed to summarize to GitHub: {:?}.", e),
+                                    })
+                                    .unwrap(),
+                                },
+                                Err(e) => serde_json::to_string(&Response {
+                                    content: &format!(
+                                        "Failed to parse summary, expected `summarize-to-github <first message> [<last message>]`: {:?}.",
+                                        e
+                                    ),
+                                })
+                                .unwrap(),
+                            },
                             _ => {}
                         }
                     }
@@ -193,6 +357,184 @@ fn handle_command<'a>(
     })
 }
 
+/// The maximum number of messages posted by `summarize-to-github`.
+const MAX_SUMMARY_MESSAGES: usize = 50;
+
+/// Links the Zulip topic of an issue to it and, the first time, points to the topic from the
+/// issue.
+///
+/// Only the topic named after the issue (`Issue::zulip_topic`) is linked, as other topics may be
+/// shared by several issues, e.g. a `nominations` topic.
+pub async fn link_topic(
+    ctx: &Context,
+    issue: &github::Issue,
+    stream_id: u64,
+) -> anyhow::Result<()> {
+    let topic = issue.zulip_topic();
+    let repo = issue.repository().to_string();
+    if !zulip_topics::link(&ctx.db, stream_id, &topic, &repo, issue.number).await? {
+        return Ok(());
+    }
+    let url = Recipient::Stream {
+        id: stream_id,
+        topic: &topic,
+    }
+    .url(&ctx.zulip);
+    issue
+        .post_comment(
+            &ctx.github,
+            &format!("This is being discussed in [a topic on Zulip]({}).", url),
+        )
+        .await
+}
+
+/// Parses the first and the optional last message of `summarize-to-github`.
+fn parse_summary_range<'a>(
+    mut words: impl Iterator<Item = &'a str>,
+) -> anyhow::Result<(u64, Option<u64>)> {
+    let first = match words.next() {
+        Some(word) => parse_message_id(word)?,
+        None => anyhow::bail!("no first message"),
+    };
+    let last = match words.next() {
+        Some(word) => Some(parse_message_id(word)?),
+        None => None,
+    };
+    if words.next().is_some() {
+        anyhow::bail!("too many words");
+    }
+    Ok((first, last))
+}
+
+/// Posts a range of messages of the current topic to the GitHub issue linked to it.
+async fn summarize_to_github(
+    ctx: &Context,
+    message: &Message,
+    first: u64,
+    last: Option<u64>,
+) -> anyhow::Result<String> {
+    let stream_id = message
+        .stream_id
+        .ok_or_else(|| anyhow::format_err!("summaries of private messages are not supported"))?;
+    let topic = message
+        .subject
+        .as_deref()
+        .ok_or_else(|| anyhow::format_err!("missing topic"))?;
+
+    let (repo, number) = match zulip_topics::linked_issue(&ctx.db, stream_id, topic).await? {
+        Some(issue) => issue,
+        None => {
+            return Ok(serde_json::to_string(&Response {
+                content: "This topic is not linked to a GitHub issue.",
+            })
+            .unwrap());
+        }
+    };
+
+    #[derive(serde::Deserialize)]
+    struct MessagesApiResponse {
+        messages: Vec<TopicMessage>,
+    }
+    #[derive(serde::Deserialize)]
+    struct TopicMessage {
+        id: u64,
+        sender_full_name: String,
+        content: String,
+    }
+
+    let narrow = serde_json::json!([
+        { "operator": "stream", "operand": stream_id },
+        { "operator": "topic", "operand": topic },
+    ]);
+    let messages = ctx
+        .zulip
+        .get("messages")
+        .query(&[
+            ("anchor", first.to_string()),
+            ("num_before", String::from("0")),
+            ("num_after", (MAX_SUMMARY_MESSAGES - 1).to_string()),
+            ("narrow", narrow.to_string()),
+            ("apply_markdown", String::from("false")),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<MessagesApiResponse>()
+        .await
+        .context("getting topic messages")?
+        .messages;
+    let messages = messages
+        .into_iter()
+        .filter(|m| m.id >= first && last.map_or(true, |last| m.id <= last))
+        .collect::<Vec<_>>();
+    if messages.is_empty() {
+        anyhow::bail!("no messages in the range");
+    }
+
+    let topic_url = Recipient::Stream {
+        id: stream_id,
+        topic,
+    }
+    .url(&ctx.zulip);
+    let comment = summary_comment(
+        &topic_url,
+        &message.sender_full_name,
+        messages
+            .iter()
+            .map(|m| (m.sender_full_name.as_str(), m.content.as_str())),
+    );
+    let issue = github::Repository { full_name: repo }
+        .get_issue(&ctx.github, number)
+        .await?;
+    issue.post_comment(&ctx.github, &comment).await?;
+
+    Ok(serde_json::to_string(&Response {
+        content: &format!(
+            "Posted {} messages to [{}]({}).",
+            messages.len(),
+            issue.global_id(),
+            issue.html_url
+        ),
+    })
+    .unwrap())
+}
+
+/// Formats the GitHub comment quoting the `(sender, content)` messages of a topic.
+fn summary_comment<'a>(
+    topic_url: &str,
+    poster: &str,
+    messages: impl Iterator<Item = (&'a str, &'a str)>,
+) -> String {
+    let mut comment = format!(
+        "Summary of [the discussion on Zulip]({}), posted by {}:\n",
+        topic_url,
+        neutralize_mentions(poster)
+    );
+    for (sender, content) in messages {
+        write!(
+            comment,
+            "\n> **{}**: {}\n",
+            neutralize_mentions(sender),
+            neutralize_mentions(content.trim()).replace('\n', "\n> ")
+        )
+        .unwrap();
+    }
+    comment
+}
+
+/// Keeps the `@` of Zulip text from pinging GitHub users or running bot commands once posted
+/// on GitHub, by following it with a zero-width joiner.
+fn neutralize_mentions(text: &str) -> String {
+    text.replace('@', "@\u{200d}")
+}
+
+/// Parses the ID of a message, given as is or as a link to it.
+fn parse_message_id(word: &str) -> anyhow::Result<u64> {
+    let id = word.rsplit("/near/").next().unwrap().trim_end_matches('/');
+    id.parse()
+        .with_context(|| format!("`{}` is not a message or a link to one", word))
+}
+
 // This does two things:
 //  * execute the command for the other user
 //  * tell the user executed for that a command was run as them by the user
@@ -234,15 +576,7 @@ async fn execute_for_other_user(
         assert_eq!(command.pop(), Some(' ')); // pop trailing space
         command
     };
-    let bot_api_token = env::var("ZULIP_API_TOKEN").expect("ZULIP_API_TOKEN");
-
-    let members = ctx
-        .github
-        .raw()
-        .get("https://rust-lang.zulipchat.com/api/v1/users")
-        .basic_auth(BOT_EMAIL, Some(&bot_api_token))
-        .send()
-        .await;
+    let members = ctx.zulip.get("users").send().await;
     let members = match members {
         Ok(members) => members,
         Err(e) => {
@@ -308,28 +642,12 @@ async fn execute_for_other_user(
         sender, command, output_msg
     );
 
-    let res = MessageApiRequest {
-        recipient: Recipient::Private {
-            id: user.user_id,
-            email: &user.email,
-        },
-        content: &message,
-    }
-    .send(ctx.github.raw())
-    .await;
-
-    match res {
-        Ok(resp) => {
-            if !resp.status().is_success() {
-                log::error!(
-                    "Failed to notify real user about command: response: {:?}",
-                    resp
-                );
-            }
-        }
-        Err(err) => {
-            log::error!("Failed to notify real user about command: {:?}", err);
-        }
+    let recipient = Recipient::Private {
+        id: user.user_id,
+        email: &user.email,
+    };
+    if let Err(err) = ctx.zulip.direct_message(recipient, &message).await {
+        log::error!("Failed to notify real user about command: {:?}", err);
     }
 
     Ok(output)
@@ -361,9 +679,25 @@ pub enum Recipient<'a> {
         #[serde(rename = "to")]
         email: &'a str,
     },
+    /// A direct message to a user known only by email.
+    #[serde(rename = "private")]
+    Email {
+        #[serde(rename = "to")]
+        email: &'a str,
+    },
+    /// A direct message to a user known only by ID.
+    #[serde(rename = "private")]
+    User {
+        #[serde(rename = "to")]
+        id: u64,
+    },
 }
 
 impl Recipient<'_> {
+    pub fn url(&self, zulip: &ZulipClient) -> String {
+        format!("{}/#narrow/{}", zulip.realm(), self.narrow())
+    }
+
     pub fn narrow(&self) -> String {
         match self {
             Recipient::Stream { id, topic } => {
@@ -388,6 +722,8 @@ impl Recipient<'_> {
                 format!("stream/{}-xxx/topic/{}", id, encoded_topic)
             }
             Recipient::Private { id, .. } => format!("pm-with/{}-xxx", id),
+            Recipient::Email { email } => format!("pm-with/{}", email),
+            Recipient::User { id } => format!("pm-with/{}-xxx", id),
         }
     }
 }
@@ -419,6 +755,27 @@ fn test_encode() {
     check_encode("áé…", ".C3.A1.C3.A9.E2.80.A6");
 }
 
+#[test]
+fn test_realm() {
+    let zulip = |realm| {
+        ZulipClient::new(
+            reqwest::Client::new(),
+            realm,
+            BOT_EMAIL.to_owned(),
+            String::new(),
+            String::new(),
+        )
+    };
+    let local = zulip("http://127.0.0.1:9991/").unwrap();
+    let recipient = Recipient::Stream { id: 1, topic: "a" };
+    assert_eq!(
+        recipient.url(&local),
+        "http://127.0.0.1:9991/#narrow/stream/1-xxx/topic/a"
+    );
+    assert!(zulip("rust-lang.zulipchat.com").is_err());
+    assert!(zulip("ftp://rust-lang.zulipchat.com").is_err());
+}
+
 #[derive(serde::Serialize)]
 pub struct MessageApiRequest<'a> {
     pub recipient: Recipient<'a>,
@@ -426,16 +783,11 @@ pub struct MessageApiRequest<'a> {
 }
 
 impl<'a> MessageApiRequest<'a> {
-    pub fn url(&self) -> String {
-        format!(
-            "https://rust-lang.zulipchat.com/#narrow/{}",
-            self.recipient.narrow()
-        )
+    pub fn url(&self, zulip: &ZulipClient) -> String {
+        self.recipient.url(zulip)
     }
 
-    pub async fn send(&self, client: &reqwest::Client) -> anyhow::Result<reqwest::Response> {
-        let bot_api_token = env::var("ZULIP_API_TOKEN").expect("ZULIP_API_TOKEN");
-
+    pub async fn send(&self, zulip: &ZulipClient) -> anyhow::Result<reqwest::Response> {
         #[derive(serde::Serialize)]
         struct SerializedApi<'a> {
             #[serde(rename = "type")]
@@ -446,21 +798,28 @@ impl<'a> MessageApiRequest<'a> {
             content: &'a str,
         }
 
-        Ok(client
-            .post("https://rust-lang.zulipchat.com/api/v1/messages")
-            .basic_auth(BOT_EMAIL, Some(&bot_api_token))
+        Ok(zulip
+            .post("messages")
             .form(&SerializedApi {
                 type_: match self.recipient {
                     Recipient::Stream { .. } => "stream",
-                    Recipient::Private { .. } => "private",
+                    Recipient::Private { .. }
+                    | Recipient::Email { .. }
+                    | Recipient::User { .. } => "private",
                 },
                 to: match self.recipient {
                     Recipient::Stream { id, .. } => id.to_string(),
-                    Recipient::Private { email, .. } => email.to_string(),
+                    Recipient::Private { email, .. } | Recipient::Email { email } => {
+                        email.to_string()
+                    }
+                    // Direct messages can be addressed to a JSON list of user IDs.
+                    Recipient::User { id } => format!("[{}]", id),
                 },
                 topic: match self.recipient {
                     Recipient::Stream { topic, .. } => Some(topic),
-                    Recipient::Private { .. } => None,
+                    Recipient::Private { .. }
+                    | Recipient::Email { .. }
+                    | Recipient::User { .. } => None,
                 },
                 content: self.content,
             })
@@ -523,6 +882,122 @@ async fn acknowledge(gh_id: i64, mut words: impl Iterator<Item = &str>) -> anyho
     }
 }
 
+/// Opts the user in to or out of the `@rustbot ping <team>` pings of a team.
+async fn ping_preference(
+    ctx: &Context,
+    gh_id: i64,
+    mut words: impl Iterator<Item = &str>,
+    opt_in: bool,
+) -> anyhow::Result<String> {
+    let team = match words.next() {
+        Some(team) => team,
+        None => anyhow::bail!("team not present"),
+    };
+    if words.next().is_some() {
+        anyhow::bail!("too many words");
+    }
+    if github::get_team(&ctx.github, team).await?.is_none() {
+        return Ok(serde_json::to_string(&Response {
+            content: &format!("The team `{}` does not exist in the team repository.", team),
+        })
+        .unwrap());
+    }
+
+    let content = if opt_in {
+        if pings::opt_in(&ctx.db, gh_id, team).await? {
+            format!("You will be pinged again when `{}` is pinged.", team)
+        } else {
+            format!("You had not opted out of pings to `{}`.", team)
+        }
+    } else {
+        pings::opt_out(&ctx.db, gh_id, team).await?;
+        format!(
+            "You will no longer be pinged when `{}` is pinged; use `ping-opt-in {}` to undo this.",
+            team, team
+        )
+    };
+    Ok(serde_json::to_string(&Response { content: &content }).unwrap())
+}
+
+/// The number of label changes shown by `label-history`, most recent last.
+const LABEL_HISTORY_LEN: usize = 30;
+
+async fn label_history(
+    ctx: &Context,
+    mut words: impl Iterator<Item = &str>,
+) -> anyhow::Result<String> {
+    let issue = match words.next() {
+        Some(issue) => issue,
+        None => anyhow::bail!("issue not present"),
+    };
+    if words.next().is_some() {
+        anyhow::bail!("too many words");
+    }
+    let (repo, number) = match issue.rfind('#') {
+        Some(idx) if issue[..idx].contains('/') => (
+            &issue[..idx],
+            issue[idx + 1..]
+                .parse::<u64>()
+                .context("invalid issue number")?,
+        ),
+        _ => anyhow::bail!("invalid issue reference"),
+    };
+
+    let changes = get_label_changes(&ctx.db, repo, number).await?;
+    if changes.is_empty() {
+        return Ok(serde_json::to_string(&Response {
+            content: &format!("No label changes were recorded for {}#{}.", repo, number),
+        })
+        .unwrap());
+    }
+
+    let mut content = String::new();
+    if changes.len() > LABEL_HISTORY_LEN {
+        writeln!(
+            content,
+            "Showing the last {} of {} label changes; see `/labels/history?repo={}&issue={}` for all of them.",
+            LABEL_HISTORY_LEN,
+            changes.len(),
+            repo,
+            number
+        )
+        .unwrap();
+    }
+    for change in &changes[changes.len().saturating_sub(LABEL_HISTORY_LEN)..] {
+        let mut diff = Vec::new();
+        let added = change.added();
+        if !added.is_empty() {
+            diff.push(format!("added `{}`", added.join("`, `")));
+        }
+        let removed = change.removed();
+        if !removed.is_empty() {
+            diff.push(format!("removed `{}`", removed.join("`, `")));
+        }
+        if diff.is_empty() {
+            diff.push(String::from("no change"));
+        }
+        write!(
+            content,
+            "- {}: {} by {} via {}",
+            change.time.format("%Y-%m-%d %H:%M %:z"),
+            diff.join(", "),
+            change.actor.as_deref().unwrap_or("unknown user"),
+            change
+                .handler
+                .as_deref()
+                .map(|h| format!("`{}`", h))
+                .unwrap_or_else(|| String::from("GitHub")),
+        )
+        .unwrap();
+        if let Some(url) = &change.comment_url {
+            write!(content, " ([comment]({}))", url).unwrap();
+        }
+        content.push('\n');
+    }
+
+    Ok(serde_json::to_string(&Response { content: &content }).unwrap())
+}
+
 async fn add_notification(
     ctx: &Context,
     gh_id: i64,
@@ -645,32 +1120,244 @@ async fn move_notification(
     }
 }
 
+async fn snooze_notification(
+    gh_id: i64,
+    mut words: impl Iterator<Item = &str>,
+) -> anyhow::Result<String> {
+    let idx = notification_index(words.next())?;
+    let duration = match words.next() {
+        Some(duration) => parse_duration(duration)?,
+        None => anyhow::bail!("duration not present"),
+    };
+    if words.next().is_some() {
+        anyhow::bail!("too many words");
+    }
+    let until = chrono::Utc::now() + duration;
+    match notifications::snooze(&crate::db::make_client().await?, gh_id, idx, until).await {
+        Ok(()) => Ok(serde_json::to_string(&Response {
+            content: &format!(
+                "Snoozed {} until {}.",
+                idx + 1,
+                until.format("%Y-%m-%d %H:%M UTC")
+            ),
+        })
+        .unwrap()),
+        Err(e) => Ok(serde_json::to_string(&Response {
+            content: &format!("Failed to snooze: {:?}.", e),
+        })
+        .unwrap()),
+    }
+}
+
+async fn remind_notification(
+    gh_id: i64,
+    mut words: impl Iterator<Item = &str>,
+) -> anyhow::Result<String> {
+    let idx = notification_index(words.next())?;
+    let at = match words.next() {
+        Some(date) => parse_date(date, chrono::Utc::now())?,
+        None => anyhow::bail!("date not present"),
+    };
+    if words.next().is_some() {
+        anyhow::bail!("too many words");
+    }
+    match notifications::remind(&crate::db::make_client().await?, gh_id, idx, at).await {
+        Ok(()) => Ok(serde_json::to_string(&Response {
+            content: &format!(
+                "I will remind you of {} on {}.",
+                idx + 1,
+                at.format("%Y-%m-%d %H:%M UTC")
+            ),
+        })
+        .unwrap()),
+        Err(e) => Ok(serde_json::to_string(&Response {
+            content: &format!("Failed to set reminder: {:?}.", e),
+        })
+        .unwrap()),
+    }
+}
+
+/// Parses a 1-based notification index into a 0-based one.
+fn notification_index(idx: Option<&str>) -> anyhow::Result<usize> {
+    let idx = match idx {
+        Some(idx) => idx,
+        None => anyhow::bail!("idx not present"),
+    };
+    idx.parse::<usize>()
+        .context("index")?
+        .checked_sub(1)
+        .ok_or_else(|| anyhow::anyhow!("1-based indexes"))
+}
+
+/// Parses durations like `30m`, `12h`, `3d` or `2w`.
+fn parse_duration(duration: &str) -> anyhow::Result<chrono::Duration> {
+    let unit_start = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow::anyhow!("missing unit in `{}`", duration))?;
+    let (number, unit) = duration.split_at(unit_start);
+    let number = number
+        .parse::<i64>()
+        .with_context(|| format!("invalid duration `{}`", duration))?;
+    if number > 10_000 {
+        anyhow::bail!("duration `{}` is too long", duration);
+    }
+    Ok(match unit {
+        "m" | "min" | "mins" => chrono::Duration::minutes(number),
+        "h" | "hour" | "hours" => chrono::Duration::hours(number),
+        "d" | "day" | "days" => chrono::Duration::days(number),
+        "w" | "week" | "weeks" => chrono::Duration::weeks(number),
+        _ => anyhow::bail!(
+            "unknown unit `{}`, expected one of `m`, `h`, `d` or `w`",
+            unit
+        ),
+    })
+}
+
+/// Parses the date of a reminder: a day like `2020-12-24` (at midnight UTC), an RFC 3339 date
+/// and time, or a duration from `now`.
+fn parse_date(
+    date: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> anyhow::Result<chrono::DateTime<chrono::Utc>> {
+    let at = if let Ok(day) = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        chrono::DateTime::from_utc(day.and_hms(0, 0, 0), chrono::Utc)
+    } else if let Ok(time) = chrono::DateTime::parse_from_rfc3339(date) {
+        time.with_timezone(&chrono::Utc)
+    } else {
+        now + parse_duration(date)
+            .with_context(|| format!("`{}` is neither a date nor a duration", date))?
+    };
+    if at <= now {
+        anyhow::bail!("{} is in the past", date);
+    }
+    Ok(at)
+}
+
+#[test]
+fn test_summary_comment() {
+    let comment = summary_comment(
+        "https://rust-lang.zulipchat.com/#narrow/stream/1-xxx/topic/a",
+        "Alice",
+        vec![
+            ("Bob", "@rustbot label +X"),
+            ("Mallory", "cc @alice\n@rustbot claim"),
+        ]
+        .into_iter(),
+    );
+    assert!(comment.contains("> **Bob**: @\u{200d}rustbot label +X\n"));
+    assert!(!comment.contains("@alice"));
+    assert_eq!(
+        parser::command::Input::new(&comment, "rustbot").count(),
+        0,
+        "{}",
+        comment
+    );
+}
+
+#[test]
+fn test_notification_index() {
+    // The offsets of `set_time` are the positions of the listing, which are shown 1-based.
+    assert_eq!(notification_index(Some("1")).unwrap(), 0);
+    assert_eq!(notification_index(Some("3")).unwrap(), 2);
+    assert!(notification_index(Some("0")).is_err());
+    assert!(notification_index(Some("-1")).is_err());
+    assert!(notification_index(None).is_err());
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(
+        parse_duration("30m").unwrap(),
+        chrono::Duration::minutes(30)
+    );
+    assert_eq!(parse_duration("3d").unwrap(), chrono::Duration::days(3));
+    assert_eq!(
+        parse_duration("2weeks").unwrap(),
+        chrono::Duration::weeks(2)
+    );
+    assert!(parse_duration("3").is_err());
+    assert!(parse_duration("d").is_err());
+    assert!(parse_duration("3y").is_err());
+}
+
+#[test]
+fn test_parse_date() {
+    let now = chrono::DateTime::parse_from_rfc3339("2020-12-01T12:00:00Z")
+        .unwrap()
+        .with_timezone(&chrono::Utc);
+    let date = |s| parse_date(s, now).map(|d| d.to_rfc3339());
+    assert_eq!(date("2020-12-24").unwrap(), "2020-12-24T00:00:00+00:00");
+    assert_eq!(
+        date("2020-12-24T08:00:00+01:00").unwrap(),
+        "2020-12-24T07:00:00+00:00"
+    );
+    assert_eq!(date("1d").unwrap(), "2020-12-02T12:00:00+00:00");
+    assert!(date("2020-11-30").is_err());
+    assert!(date("tomorrow").is_err());
+}
+
+/// Sends the reminders which are due as direct messages on Zulip.
+pub async fn send_reminders(ctx: &Context) -> anyhow::Result<()> {
+    for reminder in notifications::due_reminders(&ctx.db).await? {
+        let zulip_id = match to_zulip_id(&ctx.github, reminder.user_id).await? {
+            Some(id) => id,
+            None => {
+                log::warn!(
+                    "no Zulip ID for GitHub user {}, dropping reminder",
+                    reminder.user_id
+                );
+                notifications::clear_reminder(&ctx.db, reminder.notification_id).await?;
+                continue;
+            }
+        };
+        let content = format!(
+            "Reminder: [{}]({}){}",
+            reminder
+                .short_description
+                .as_deref()
+                .unwrap_or(&reminder.origin_url),
+            reminder.origin_url,
+            reminder
+                .metadata
+                .as_ref()
+                .map_or(String::new(), |m| format!(" ({})", m)),
+        );
+        let recipient = Recipient::User {
+            id: zulip_id as u64,
+        };
+        match ctx.zulip.direct_message(recipient, &content).await {
+            Ok(()) => notifications::clear_reminder(&ctx.db, reminder.notification_id).await?,
+            // Left in place to be retried by the next run.
+            Err(e) => log::error!(
+                "failed to send reminder {}: {:?}",
+                reminder.notification_id,
+                e
+            ),
+        }
+    }
+    Ok(())
+}
+
 #[derive(serde::Serialize, Debug)]
 struct ResponseNotRequired {
     response_not_required: bool,
 }
 
 #[derive(serde::Deserialize, Debug)]
-struct SentMessage {
-    id: u64,
+pub(crate) struct SentMessage {
+    pub(crate) id: u64,
 }
 
 #[derive(serde::Serialize, Debug, Copy, Clone)]
-struct AddReaction<'a> {
-    message_id: u64,
-    emoji_name: &'a str,
+pub(crate) struct AddReaction<'a> {
+    pub(crate) message_id: u64,
+    pub(crate) emoji_name: &'a str,
 }
 
 impl<'a> AddReaction<'a> {
-    pub async fn send(self, client: &reqwest::Client) -> anyhow::Result<reqwest::Response> {
-        let bot_api_token = env::var("ZULIP_API_TOKEN").expect("ZULIP_API_TOKEN");
-
-        Ok(client
-            .post(&format!(
-                "https://rust-lang.zulipchat.com/api/v1/messages/{}/reactions",
-                self.message_id
-            ))
-            .basic_auth(BOT_EMAIL, Some(&bot_api_token))
+    pub async fn send(self, zulip: &ZulipClient) -> anyhow::Result<reqwest::Response> {
+        Ok(zulip
+            .post(&format!("messages/{}/reactions", self.message_id))
             .form(&self)
             .send()
             .await?)
@@ -718,7 +1405,7 @@ async fn post_waiter(
         },
         content: waiting.primary,
     }
-    .send(ctx.github.raw())
+    .send(&ctx.zulip)
     .await?;
     let body = posted.text().await?;
     let message_id = serde_json::from_str::<SentMessage>(&body)
@@ -730,7 +1417,7 @@ async fn post_waiter(
             message_id,
             emoji_name: reaction,
         }
-        .send(&ctx.github.raw())
+        .send(&ctx.zulip)
         .await
         .context("emoji reaction failed")?;
     }